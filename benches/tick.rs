@@ -0,0 +1,117 @@
+// how the tick, and the pieces it's made of, cost at different population and food densities
+// run with: cargo bench, or cargo bench -- neighbours to pick a group
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use life_3d::block::{Block, BlockType, FoodSource};
+use life_3d::cell::CellType;
+use life_3d::organism::Organism;
+use life_3d::population::Population;
+use life_3d::simulation::{random_organism, update_world, Outcome, Simulation};
+use life_3d::snapshot::Snapshot;
+use life_3d::spatial::{FoodIndex, Surroundings};
+use life_3d::world::World;
+
+static WORLD_SIZE: usize = 128;
+static SPREAD: i8 = 48;  // organisms and food are scattered this far from the middle on every axis
+static SEED: u64 = 42;
+static DENSITIES: [(usize, usize); 4] = [(100, 100), (1000, 1000), (1000, 10000), (10000, 10000)]; // (organisms, food blocks)
+static CROWDS: [(usize, usize); 1] = [(100000, 100000)]; // only ticked, the other groups would take minutes at this size
+
+struct Setup {
+    world: World,
+    organisms: Vec<Organism>,
+    blocks: Vec<Block>,
+}
+
+fn seeded(organisms: usize, food: usize) -> Setup {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let position = |rng: &mut StdRng| (rng.gen_range(-SPREAD..SPREAD), rng.gen_range(-SPREAD..SPREAD), rng.gen_range(-SPREAD..SPREAD));
+    let organisms = (0..organisms).map(|_| {
+        let mut organism = random_organism(&mut rng);
+        (organism.x, organism.y, organism.z) = position(&mut rng);
+        organism
+    }).collect();
+    let blocks = (0..food).map(|_| {
+        let (x, y, z) = position(&mut rng);
+        Block::new(BlockType::Food(FoodSource::Producer), x, y, z)
+    }).collect();
+    Setup { world: World::new(WORLD_SIZE, WORLD_SIZE, WORLD_SIZE), organisms, blocks }
+}
+
+fn label(organisms: usize, food: usize) -> String {
+    format!("{} organisms, {} food", organisms, food)
+}
+
+fn tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tick");
+    group.sample_size(10);
+    for (organisms, food) in DENSITIES.into_iter().chain(CROWDS) {
+        let mut setup = seeded(organisms, food);
+        let population: Population = setup.organisms.into_iter().collect();
+        for block in &setup.blocks {
+            setup.world.place_block(block); // the tick expects the world to know where the food is
+        }
+        group.bench_function(BenchmarkId::from_parameter(label(organisms, food)), |b| {
+            b.iter_batched(
+                || (setup.world.clone(), population.clone(), setup.blocks.clone()),
+                |(mut world, mut population, mut blocks)| {
+                    let mut outcome = Outcome::default();
+                    // limits well above the starting numbers, so they don't cut the work short
+                    update_world(&mut population, &mut outcome, &mut blocks, usize::MAX, usize::MAX, &mut world, SEED);
+                    (world, population, blocks, outcome)
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn neighbours(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbours");
+    for (organisms, food) in DENSITIES {
+        let setup = seeded(organisms, food);
+        group.bench_function(BenchmarkId::new("index", label(organisms, food)), |b| {
+            b.iter(|| (setup.organisms.iter().cloned().collect::<Population>(), FoodIndex::of(&setup.blocks)));
+        });
+        let (population, food_index): (Population, FoodIndex) = (setup.organisms.iter().cloned().collect(), FoodIndex::of(&setup.blocks));
+        let around = Surroundings { population: &population, food: &food_index };
+        group.bench_function(BenchmarkId::new("nearby organisms, everyone", label(organisms, food)), |b| {
+            b.iter(|| setup.organisms.iter().map(|organism| around.organisms_within(organism.x, organism.y, organism.z, 1).count()).sum::<usize>());
+        });
+        group.bench_function(BenchmarkId::new("decide, everyone", label(organisms, food)), |b| {
+            b.iter(|| setup.organisms.iter()
+                .filter_map(|organism| organism.cells.iter().find(|cell| matches!(cell.cell_type, CellType::Eye(_))).map(|eye| organism.decide(&around, eye).score))
+                .sum::<f32>());
+        });
+    }
+    group.finish();
+}
+
+fn reproduction(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let organism = random_organism(&mut rng);
+    c.bench_function("offspring", |b| b.iter(|| organism.offspring(&mut rng)));
+    c.bench_function("mutate", |b| {
+        b.iter_batched(|| organism.clone(), |mut organism| { organism.mutate(&mut rng); organism }, BatchSize::SmallInput);
+    });
+}
+
+fn snapshots(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+    for (organisms, food) in DENSITIES {
+        let setup = seeded(organisms, food);
+        let sim = Simulation::new(setup.world, setup.organisms, setup.blocks, SEED);
+        group.bench_function(BenchmarkId::new("take", label(organisms, food)), |b| b.iter(|| Snapshot::of(&sim)));
+        let snapshot = Snapshot::of(&sim);
+        group.bench_function(BenchmarkId::new("serialize", label(organisms, food)), |b| b.iter(|| snapshot.to_ron().unwrap()));
+        let text = snapshot.to_ron().unwrap();
+        group.bench_function(BenchmarkId::new("deserialize", label(organisms, food)), |b| b.iter(|| Snapshot::from_ron(&text).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tick, neighbours, reproduction, snapshots);
+criterion_main!(benches);
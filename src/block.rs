@@ -1,16 +1,48 @@
 // blocks not attached to any organism
 
+use serde::{Deserialize, Serialize};
+
+// nutrition is the energy an organism gains by eating a food block
+pub static PRODUCER_FOOD_NUTRITION: u8 = 10;
+pub static CARCASS_FOOD_NUTRITION: u8 = 20;
+// food loses one point of nutrition every x ticks
+pub static PRODUCER_FOOD_DECAY_INTERVAL: u16 = 50;
+pub static CARCASS_FOOD_DECAY_INTERVAL: u16 = 10;  // carcasses rot faster than plants
+
+#[derive(Debug)]
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize)]
+pub enum FoodSource {
+    Producer, // grown by a producer cell
+    Carcass,  // left behind by a dead organism
+}
+
 #[derive(PartialEq)]
 #[derive(Clone)]
+#[derive(Serialize, Deserialize)]
 pub enum BlockType {
-    Food,
+    Food(FoodSource),
     Wall,
 }
 
+impl BlockType {
+    pub fn color(&self) -> (f32, f32, f32) {
+        match self {
+            BlockType::Food(FoodSource::Producer) => (0.2, 0.3, 0.3),
+            BlockType::Food(FoodSource::Carcass) => (0.4, 0.2, 0.1), // carcasses are brown
+            BlockType::Wall => (0.6, 0.6, 0.6),                      // walls are grey
+        }
+    }
+}
+
 #[derive(PartialEq)]
 #[derive(Clone)]
+#[derive(Serialize, Deserialize)]
 pub struct Block {
     pub block_type: BlockType,
+    pub nutrition: u8, // walls have none
+    pub age: u16,      // ticks since the block was created
     pub x: i8,
     pub y: i8,
     pub z: i8,
@@ -18,11 +50,38 @@ pub struct Block {
 
 impl Block {
     pub fn new(block_type: BlockType, x: i8, y: i8, z: i8) -> Block {
+        let nutrition = match block_type {
+            BlockType::Food(FoodSource::Producer) => PRODUCER_FOOD_NUTRITION,
+            BlockType::Food(FoodSource::Carcass) => CARCASS_FOOD_NUTRITION,
+            BlockType::Wall => 0,
+        };
         Block {
             block_type,
+            nutrition,
+            age: 0,
             x,
             y,
             z,
         }
     }
-}
\ No newline at end of file
+    pub fn decay(&mut self) -> u8 { // ages the block by one tick, returns the nutrition lost
+        self.age = self.age.saturating_add(1);
+        let interval = match self.block_type {
+            BlockType::Food(FoodSource::Producer) => PRODUCER_FOOD_DECAY_INTERVAL,
+            BlockType::Food(FoodSource::Carcass) => CARCASS_FOOD_DECAY_INTERVAL,
+            BlockType::Wall => return 0,
+        };
+        if self.nutrition > 0 && self.age.is_multiple_of(interval) {
+            self.nutrition -= 1;
+            1
+        } else {
+            0
+        }
+    }
+    pub fn feed(&self, energy: u8) -> u8 { // an eater's energy after eating this, unless it was already full
+        if energy < 100 { energy.saturating_add(self.nutrition) } else { energy }
+    }
+    pub fn is_rotten(&self) -> bool { // fully decayed food disappears
+        matches!(self.block_type, BlockType::Food(_)) && self.nutrition == 0
+    }
+}
@@ -0,0 +1,170 @@
+// viewer cameras: orbiting the world, following an organism, or flying freely
+
+use kiss3d::camera::{ArcBall, Camera, FirstPerson};
+use kiss3d::event::Key;
+use kiss3d::nalgebra::Point3;
+use crate::snapshot::Snapshot;
+
+static HOME_EYE: (f32, f32, f32) = (60.0, 40.0, 60.0); // where reset puts the camera, looking at the origin
+static AUTO_ROTATE_STEP: f32 = 0.003;  // radians of yaw per frame
+static FOLLOW_SMOOTHING: f32 = 0.1;    // fraction of the way to the followed organism moved per frame
+static BOOKMARK_SLOTS: usize = 4;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum CameraMode {
+    Orbit,  // around the world center
+    Follow, // orbit around the target organism
+    Free,   // first person, moved with the arrow keys
+}
+
+#[derive(Clone, Copy)]
+struct Bookmark {
+    mode: CameraMode,
+    eye: Point3<f32>,
+    at: Point3<f32>,
+}
+
+pub struct CameraRig {
+    pub mode: CameraMode,
+    pub auto_rotate: bool,
+    pub target: Option<u64>, // id of the organism to follow
+    orbit: ArcBall,
+    free: FirstPerson,
+    bookmarks: Vec<Option<Bookmark>>,
+}
+
+fn home_eye() -> Point3<f32> {
+    Point3::new(HOME_EYE.0, HOME_EYE.1, HOME_EYE.2)
+}
+
+impl Default for CameraRig {
+    fn default() -> CameraRig {
+        CameraRig::new()
+    }
+}
+
+impl CameraRig {
+    pub fn new() -> CameraRig {
+        let mut orbit = ArcBall::new(home_eye(), Point3::origin());
+        orbit.rebind_reset_key(None); // reset is handled here so it covers every mode
+        let mut free = FirstPerson::new_with_frustrum(90.0, 0.1, 400.0, home_eye(), Point3::origin());
+        free.set_move_step(1.0);
+        free.set_pitch_step(0.01);
+        free.set_yaw_step(0.01);
+        CameraRig {
+            mode: CameraMode::Orbit,
+            auto_rotate: false,
+            target: None,
+            orbit,
+            free,
+            bookmarks: vec![None; BOOKMARK_SLOTS],
+        }
+    }
+
+    pub fn camera(&mut self) -> &mut dyn Camera {
+        match self.mode {
+            CameraMode::Orbit | CameraMode::Follow => &mut self.orbit,
+            CameraMode::Free => &mut self.free,
+        }
+    }
+
+    pub fn eye_and_at(&self) -> (Point3<f32>, Point3<f32>) {
+        match self.mode {
+            CameraMode::Orbit | CameraMode::Follow => (self.orbit.eye(), self.orbit.at()),
+            CameraMode::Free => (self.free.eye(), self.free.at()),
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) { // switches without jumping, the new camera starts where the old one was
+        let (eye, at) = self.eye_and_at();
+        match mode {
+            CameraMode::Orbit => self.orbit.look_at(eye, Point3::origin()),
+            CameraMode::Follow => self.orbit.look_at(eye, at),
+            CameraMode::Free => self.free.look_at(eye, at),
+        }
+        self.mode = mode;
+    }
+
+    pub fn follow(&mut self, id: u64) {
+        self.target = Some(id);
+        if self.mode != CameraMode::Follow {
+            self.set_mode(CameraMode::Follow);
+        }
+    }
+
+    pub fn follow_next(&mut self, sim: &Snapshot) { // cycles the target through the organisms in order
+        let next = match self.target.and_then(|id| sim.organisms.iter().position(|organism| organism.id == id)) {
+            Some(i) => sim.organisms.get(i + 1).or(sim.organisms.first()),
+            None => sim.organisms.first(),
+        };
+        if let Some(organism) = next {
+            self.follow(organism.id);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.mode = CameraMode::Orbit;
+        self.auto_rotate = false;
+        self.orbit.look_at(home_eye(), Point3::origin());
+        self.free.look_at(home_eye(), Point3::origin());
+    }
+
+    pub fn save_bookmark(&mut self, slot: usize) {
+        let (eye, at) = self.eye_and_at();
+        if let Some(bookmark) = self.bookmarks.get_mut(slot) {
+            *bookmark = Some(Bookmark { mode: self.mode, eye, at });
+        }
+    }
+
+    pub fn load_bookmark(&mut self, slot: usize) -> bool { // false if nothing is saved there
+        let Some(Some(bookmark)) = self.bookmarks.get(slot).cloned() else {
+            return false;
+        };
+        self.mode = bookmark.mode;
+        match bookmark.mode {
+            CameraMode::Orbit | CameraMode::Follow => self.orbit.look_at(bookmark.eye, bookmark.at),
+            CameraMode::Free => self.free.look_at(bookmark.eye, bookmark.at),
+        }
+        true
+    }
+
+    pub fn update(&mut self, sim: &Snapshot) { // call once per frame, before rendering
+        if self.mode == CameraMode::Follow {
+            let target = self.target.and_then(|id| sim.organisms.iter().find(|organism| organism.id == id));
+            match target {
+                Some(organism) => {
+                    let at = self.orbit.at();
+                    let goal = Point3::new(organism.x as f32, organism.y as f32, organism.z as f32);
+                    self.orbit.set_at(at + (goal - at) * FOLLOW_SMOOTHING);
+                }
+                None => { // it died, go back to orbiting the world
+                    self.target = None;
+                    self.set_mode(CameraMode::Orbit);
+                }
+            }
+        }
+        if self.auto_rotate && self.mode != CameraMode::Free {
+            self.orbit.set_yaw(self.orbit.yaw() + AUTO_ROTATE_STEP);
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mode = match (self.mode, self.target) {
+            (CameraMode::Follow, Some(id)) => format!("following #{}", id),
+            (mode, _) => format!("{:?}", mode).to_lowercase(),
+        };
+        if self.auto_rotate { format!("{}, rotating", mode) } else { mode }
+    }
+}
+
+pub fn bookmark_slot(key: Key) -> Option<usize> { // number keys 1 to 4
+    match key {
+        Key::Key1 => Some(0),
+        Key::Key2 => Some(1),
+        Key::Key3 => Some(2),
+        Key::Key4 => Some(3),
+        _ => None,
+    }
+}
@@ -0,0 +1,116 @@
+// human readable organism definitions (RON) and the bundled preset library
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use crate::cell::{Brain, Cell, CellType, Eye, Producer};
+use crate::organism::Organism;
+
+// presets bundled into the binary, by name
+static PRESETS: [(&str, &str); 5] = [
+    ("blob", include_str!("../presets/blob.ron")),
+    ("mover", include_str!("../presets/mover.ron")),
+    ("plant", include_str!("../presets/plant.ron")),
+    ("grazer", include_str!("../presets/grazer.ron")),
+    ("hunter", include_str!("../presets/hunter.ron")),
+];
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize)]
+pub enum CellKind { // every cell type except the brain, which is implied at (0, 0, 0)
+    Eye,
+    Armor,
+    Killer,
+    Eater,
+    Mover,
+    Producer,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct CellDefinition {
+    pub kind: CellKind,
+    #[serde(default)]
+    pub rotation: i8, // 0 to 5: x, -x, y, -y, z, -z
+    pub position: (i8, i8, i8), // relative to the brain
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct BrainDefinition {
+    pub aggression: f32,
+    pub hunger: f32,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct OrganismDefinition {
+    pub name: String,
+    pub brain: BrainDefinition,
+    pub cells: Vec<CellDefinition>,
+}
+
+impl OrganismDefinition {
+    pub fn parse(text: &str) -> Result<OrganismDefinition> {
+        ron::from_str(text).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+    }
+    pub fn load(path: &str) -> Result<OrganismDefinition> {
+        OrganismDefinition::parse(&fs::read_to_string(path)?)
+    }
+    pub fn save(&self, path: &str) -> Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        fs::write(path, text)
+    }
+    pub fn preset(name: &str) -> Option<OrganismDefinition> {
+        PRESETS.iter()
+            .find(|(preset_name, _)| *preset_name == name)
+            .map(|(_, text)| OrganismDefinition::parse(text).expect("bundled presets are valid"))
+    }
+    pub fn preset_names() -> Vec<&'static str> {
+        PRESETS.iter().map(|(name, _)| *name).collect()
+    }
+    pub fn to_organism(&self) -> Organism {
+        let mut organism = Organism::new();
+        organism.cells = vec![Cell::new(CellType::Brain(Brain {
+            aggression: self.brain.aggression.clamp(0.0, 1.0),
+            hunger: self.brain.hunger.clamp(0.0, 1.0),
+        }), 0, 0, 0, 0)];
+        for cell in &self.cells {
+            let cell_type = match cell.kind {
+                CellKind::Eye => CellType::Eye(Eye {}),
+                CellKind::Armor => CellType::Armor,
+                CellKind::Killer => CellType::Killer,
+                CellKind::Eater => CellType::Eater,
+                CellKind::Mover => CellType::Mover,
+                CellKind::Producer => CellType::Producer(Producer {}),
+            };
+            let (x, y, z) = cell.position;
+            organism.cells.push(Cell::new(cell_type, cell.rotation.rem_euclid(6), x, y, z));
+        }
+        organism
+    }
+    pub fn from_organism(name: &str, organism: &Organism) -> OrganismDefinition { // the brain cell is assumed to sit at the origin
+        let brain = BrainDefinition {
+            aggression: organism.get_aggression().unwrap_or(0.5),
+            hunger: organism.get_hunger().unwrap_or(0.5),
+        };
+        let cells = organism.cells.iter().filter_map(|cell| {
+            let kind = match cell.cell_type {
+                CellType::Brain(_) => return None,
+                CellType::Eye(_) => CellKind::Eye,
+                CellType::Armor => CellKind::Armor,
+                CellType::Killer => CellKind::Killer,
+                CellType::Eater => CellKind::Eater,
+                CellType::Mover => CellKind::Mover,
+                CellType::Producer(_) => CellKind::Producer,
+            };
+            Some(CellDefinition { kind, rotation: cell.rotation, position: (cell.local_x, cell.local_y, cell.local_z) })
+        }).collect();
+        OrganismDefinition { name: name.to_string(), brain, cells }
+    }
+}
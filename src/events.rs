@@ -0,0 +1,250 @@
+// what happens in a run, as it happens: written a line per tick after a snapshot of the start,
+// and played back onto that snapshot to watch the run again without simulating it
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use serde::{Deserialize, Serialize};
+use crate::block::Block;
+use crate::cell::Cell;
+use crate::organism::{Organism, REPRODUCTION_COST};
+use crate::simulation::{ATTACK_DAMAGE, ENERGY_PER_TICK};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize)]
+pub enum Cause {
+    Killed,   // attacked until its health ran out
+    Poisoned, // health ran out in toxins, without being attacked
+    Starved,
+    OldAge,
+    Removed,  // by a command: a meteor, or clearing a region
+    Culled,
+}
+
+// the tick logs these in the order it does them, and playing them back in that order gives the same state;
+// within a tick: commands first, then attacks, eating, food, moves and mutations, ageing, deaths, and births last
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+pub enum Event {
+    Born(Box<Organism>),                              // with its parent, or none when placed; a parent pays for its child
+    Died { id: u64, cause: Cause, carcass: usize },   // carcass is how many of its cells turned into food
+    Mutated { id: u64, cells: Vec<Cell> },
+    Ate { id: u64, block: usize },                    // block is where the food was in the list at the start of the tick
+    Attacked { attacker: u64, target: u64 },
+    Moved { id: u64, x: i8, y: i8, z: i8, cells: Option<Vec<Cell>> }, // cells when it turned as well
+    Produced(Block),                                  // food from a producer
+    Placed(Block),                                    // food or wall put down by a command
+    Unplaced { block: usize },                        // taken away by a command, from where it is in the list right now
+    Aged,                                             // everyone alive is a tick older and has paid for it
+    Uncomfortable { id: u64 },                        // paid extra for being too hot or cold
+    Sickened { id: u64 },                             // lost health to toxins
+}
+
+pub fn record(events: &mut Option<Vec<Event>>, event: impl FnOnce() -> Event) { // only builds the event when recording
+    if let Some(events) = events {
+        events.push(event());
+    }
+}
+
+pub struct EventLog {
+    file: BufWriter<File>,
+}
+
+impl EventLog {
+    pub fn create(path: &str, start: &Snapshot) -> Result<EventLog> { // the snapshot goes on the first line
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", ron::to_string(start).map_err(Error::other)?)?;
+        Ok(EventLog { file })
+    }
+    pub fn write(&mut self, tick: u64, events: &[Event]) -> Result<()> { // tick is the one the events led to
+        writeln!(self.file, "{}", ron::to_string(&(tick, events)).map_err(Error::other)?)?;
+        self.file.flush()
+    }
+}
+
+pub struct Replay {
+    state: Snapshot,
+    ticks: Vec<(u64, Vec<Event>)>,
+    next: usize,              // in ticks
+    index: HashMap<u64, usize>, // organism ids to where they are in state
+    eaten: HashSet<usize>,    // blocks eaten this tick, cleared away at the end of it
+    dead: HashSet<u64>,       // likewise
+}
+
+impl Replay {
+    pub fn new(start: Snapshot, ticks: Vec<(u64, Vec<Event>)>) -> Replay {
+        Replay { state: start, ticks, next: 0, index: HashMap::new(), eaten: HashSet::new(), dead: HashSet::new() }
+    }
+    pub fn load(path: &str) -> Result<Replay> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        let start = Snapshot::from_ron(lines.next().ok_or(Error::new(ErrorKind::InvalidData, "empty event log"))?)?;
+        let mut ticks: Vec<(u64, Vec<Event>)> = Vec::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let (tick, events): (u64, Vec<Event>) = ron::from_str(line).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            // a tick that has been seen already means the run was forked from before it; what came after is gone
+            ticks.retain(|&(earlier, _)| earlier < tick);
+            ticks.push((tick, events));
+        }
+        // played through once here, so a log that doesn't fit its snapshot is turned away rather than stopping partway
+        let mut check = Replay::new(start.clone(), ticks.clone());
+        while check.step()? {}
+        Ok(Replay::new(start, ticks))
+    }
+
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.state
+    }
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.ticks.len()
+    }
+
+    pub fn step(&mut self) -> Result<bool> { // plays back the next tick, if there is one; a log that doesn't fit ends there
+        let Some((tick, events)) = self.ticks.get(self.next) else {
+            return Ok(false);
+        };
+        let (tick, events) = (*tick, events.clone());
+        self.next += 1;
+        self.index = self.state.organisms.iter().enumerate().map(|(i, organism)| (organism.id, i)).collect();
+        for event in events {
+            if let Err(e) = self.apply(event) {
+                self.next = self.ticks.len();
+                return Err(Error::new(ErrorKind::InvalidData, format!("tick {}: {}", tick, e)));
+            }
+        }
+        // eaten food is gone once everyone has eaten; then the food decays and the dead are cleared away
+        let mut block = 0;
+        let eaten = std::mem::take(&mut self.eaten);
+        self.state.blocks.retain(|_| { block += 1; !eaten.contains(&(block - 1)) });
+        for block in &mut self.state.blocks {
+            block.decay();
+        }
+        self.state.blocks.retain(|block| !block.is_rotten());
+        let dead = std::mem::take(&mut self.dead);
+        self.state.organisms.retain(|organism| !dead.contains(&organism.id));
+        self.state.tick = tick;
+        self.state.revision += 1;
+        Ok(true)
+    }
+
+    fn organism(&mut self, id: u64) -> Option<&mut Organism> {
+        self.index.get(&id).map(|&i| &mut self.state.organisms[i])
+    }
+
+    fn apply(&mut self, event: Event) -> std::result::Result<(), String> {
+        match event {
+            Event::Born(organism) => {
+                if let Some(parent) = organism.parent.and_then(|parent| self.organism(parent)) {
+                    parent.energy = parent.energy.saturating_sub(REPRODUCTION_COST);
+                }
+                self.index.insert(organism.id, self.state.organisms.len());
+                self.state.organisms.push(*organism);
+            }
+            Event::Died { id, carcass, .. } => {
+                if let Some(organism) = self.organism(id) {
+                    let carcass: Vec<Block> = organism.kill().into_iter().take(carcass).collect();
+                    self.state.blocks.extend(carcass);
+                    self.dead.insert(id);
+                }
+            }
+            Event::Mutated { id, cells } => {
+                if let Some(organism) = self.organism(id) {
+                    organism.cells = cells;
+                }
+            }
+            Event::Ate { id, block } => {
+                let food = self.state.blocks.get(block).ok_or(format!("no block {} to eat", block))?.clone();
+                if let Some(organism) = self.organism(id) {
+                    organism.energy = food.feed(organism.energy);
+                }
+                self.eaten.insert(block);
+            }
+            Event::Attacked { target, .. } => {
+                if let Some(organism) = self.organism(target) {
+                    organism.health = organism.health.saturating_sub(ATTACK_DAMAGE);
+                }
+            }
+            Event::Moved { id, x, y, z, cells } => {
+                if let Some(organism) = self.organism(id) {
+                    (organism.x, organism.y, organism.z) = (x, y, z);
+                    if let Some(cells) = cells {
+                        organism.cells = cells;
+                    }
+                }
+            }
+            Event::Produced(block) | Event::Placed(block) => self.state.blocks.push(block),
+            Event::Unplaced { block } => {
+                if block >= self.state.blocks.len() {
+                    return Err(format!("no block {} to take away", block));
+                }
+                self.state.blocks.remove(block);
+            }
+            Event::Aged => {
+                for organism in &mut self.state.organisms {
+                    organism.lifespan = organism.lifespan.saturating_sub(1);
+                    organism.age += 1;
+                    organism.energy = organism.energy.saturating_sub(ENERGY_PER_TICK);
+                }
+            }
+            Event::Uncomfortable { id } => {
+                if let Some(organism) = self.organism(id) {
+                    organism.energy = organism.energy.saturating_sub(1);
+                }
+            }
+            Event::Sickened { id } => {
+                if let Some(organism) = self.organism(id) {
+                    organism.health = organism.health.saturating_sub(1);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definition::OrganismDefinition;
+    use crate::simulation::{Command, Simulation};
+    use crate::world::World;
+
+    #[test]
+    fn replay_follows_the_run() {
+        let mut sim = Simulation::new(World::new(64, 64, 64), Vec::new(), Vec::new(), 11);
+        for (i, name) in ["blob", "grazer", "hunter", "plant"].into_iter().enumerate() {
+            sim.schedule(0, Command::Spawn { definition: OrganismDefinition::preset(name), x: i as i8 * 6, y: 0, z: 0 });
+        }
+        sim.schedule(0, Command::Spawn { definition: None, x: -6, y: 0, z: 0 });
+        sim.schedule(40, Command::PlaceFood { x: 3, y: 1, z: 0 });
+        sim.schedule(80, Command::Cull { fraction: 0.3 });
+        sim.schedule(150, Command::Meteor { x: 0, y: 0, z: 0, radius: 4 });
+        sim.schedule(200, Command::RemoveRegion { min: (10, -5, -5), max: (20, 5, 5) });
+        sim.schedule(220, Command::Spawn { definition: OrganismDefinition::preset("grazer"), x: 0, y: 6, z: 0 });
+        sim.record_events();
+
+        let mut replay = Replay::new(Snapshot::of(&sim), Vec::new());
+        for _ in 0..300 {
+            sim.step();
+            replay.ticks.push((sim.tick, sim.take_events()));
+            assert!(replay.step().unwrap());
+            let mut expected = Snapshot::of(&sim);
+            expected.revision = replay.snapshot().revision; // commands bump the run's, a replayed tick only bumps once
+            assert_eq!(replay.snapshot().to_ron().unwrap(), expected.to_ron().unwrap(), "tick {}", sim.tick);
+        }
+        assert!(!replay.step().unwrap());
+        assert!(sim.population.len() > 5, "nothing happened to compare");
+    }
+
+    #[test]
+    fn blocks_that_arent_there_are_an_error() {
+        let start = Snapshot::of(&Simulation::new(World::new(8, 8, 8), Vec::new(), Vec::new(), 0));
+        let mut replay = Replay::new(start.clone(), vec![(1, vec![Event::Ate { id: 0, block: 3 }]), (2, Vec::new())]);
+        assert!(replay.step().is_err());
+        assert!(replay.is_finished());
+        let mut replay = Replay::new(start, vec![(1, vec![Event::Unplaced { block: 0 }])]);
+        assert!(replay.step().is_err());
+    }
+}
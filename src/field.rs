@@ -0,0 +1,247 @@
+// scalar fields (temperature, nutrients, toxins) stored per voxel of the world
+
+// default field parameters: (diffusion, decay, baseline)
+pub static TEMPERATURE_PARAMS: (f32, f32, f32) = (0.1, 0.01, 0.5);  // temperature is 0 (cold) to 1 (hot)
+pub static NUTRIENT_PARAMS: (f32, f32, f32) = (0.02, 0.0, 0.0);     // nutrients are conserved, they only spread
+pub static TOXIN_PARAMS: (f32, f32, f32) = (0.05, 0.02, 0.0);
+static FIELD_EPSILON: f32 = 0.0001; // values this close to the baseline stop being updated, and snap back to it if the field decays
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum FieldKind {
+    Temperature,
+    Nutrients,
+    Toxins,
+}
+
+impl FieldKind {
+    pub const ALL: [FieldKind; 3] = [FieldKind::Temperature, FieldKind::Nutrients, FieldKind::Toxins];
+    pub fn default_params(&self) -> (f32, f32, f32) {
+        match self {
+            FieldKind::Temperature => TEMPERATURE_PARAMS,
+            FieldKind::Nutrients => NUTRIENT_PARAMS,
+            FieldKind::Toxins => TOXIN_PARAMS,
+        }
+    }
+}
+
+pub struct Field {
+    values: Vec<f32>,  // flat, x-major: (x * height + y) * depth + z
+    scratch: Vec<f32>, // reused every update so diffusion doesn't allocate; made on the first update, and not copied by clone
+    active: Option<[(usize, usize); 3]>, // inclusive x, y, z ranges outside of which everything is within FIELD_EPSILON of the baseline
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    pub diffusion: f32, // fraction exchanged with each neighbour per tick, at most 1/6
+    pub decay: f32,     // fraction of the difference to the baseline lost per tick
+    pub baseline: f32,  // value the field relaxes towards
+}
+
+impl Clone for Field {
+    fn clone(&self) -> Field { // everything update copies out of scratch it wrote first, so there is nothing in it worth copying
+        Field { values: self.values.clone(), ..self.clone_empty() }
+    }
+}
+
+impl Field {
+    pub fn new(width: usize, height: usize, depth: usize, diffusion: f32, decay: f32, baseline: f32) -> Field {
+        Field {
+            values: vec![baseline; width * height * depth],
+            scratch: Vec::new(),
+            active: None,
+            width,
+            height,
+            depth,
+            diffusion: diffusion.clamp(0.0, 1.0 / 6.0), // larger rates make the explicit update unstable
+            decay: decay.clamp(0.0, 1.0),
+            baseline,
+        }
+    }
+    fn index(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        if x < self.width && y < self.height && z < self.depth {
+            Some((x * self.height + y) * self.depth + z)
+        } else {
+            None
+        }
+    }
+    fn mark_active(&mut self, x: usize, y: usize, z: usize) {
+        self.active = Some(match self.active {
+            Some([xs, ys, zs]) => [(xs.0.min(x), xs.1.max(x)), (ys.0.min(y), ys.1.max(y)), (zs.0.min(z), zs.1.max(z))],
+            None => [(x, x), (y, y), (z, z)],
+        });
+    }
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        match self.index(x, y, z) {
+            Some(i) => self.values[i],
+            None => self.baseline,
+        }
+    }
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: f32) {
+        if let Some(i) = self.index(x, y, z) {
+            self.values[i] = value;
+            self.mark_active(x, y, z);
+        }
+    }
+    pub fn add(&mut self, x: usize, y: usize, z: usize, amount: f32) {
+        if let Some(i) = self.index(x, y, z) {
+            self.values[i] += amount;
+            self.mark_active(x, y, z);
+        }
+    }
+    pub fn take(&mut self, x: usize, y: usize, z: usize, max_amount: f32) -> f32 { // removes up to max_amount, returns how much was taken
+        if let Some(i) = self.index(x, y, z) {
+            let taken = self.values[i].clamp(0.0, max_amount);
+            self.values[i] -= taken;
+            self.mark_active(x, y, z);
+            taken
+        } else {
+            0.0
+        }
+    }
+    pub fn update(&mut self) { // one tick of diffusion between face neighbours, then decay towards the baseline
+        if self.diffusion == 0.0 && self.decay == 0.0 {
+            return;
+        }
+        let Some([xs, ys, zs]) = self.active else {
+            return; // the whole field is at the baseline
+        };
+        if self.scratch.len() != self.values.len() {
+            self.scratch = vec![self.baseline; self.values.len()];
+        }
+        let (w, h, d) = (self.width, self.height, self.depth);
+        // only the active region and one voxel around it can change this tick
+        let grow = |(lo, hi): (usize, usize), size: usize| (lo.saturating_sub(1), (hi + 1).min(size - 1));
+        let (xs, ys, zs) = (grow(xs, w), grow(ys, h), grow(zs, d));
+        self.active = None;
+        for x in xs.0..=xs.1 {
+            for y in ys.0..=ys.1 {
+                for z in zs.0..=zs.1 {
+                    let i = (x * h + y) * d + z;
+                    let value = self.values[i];
+                    let mut flow = 0.0;
+                    // edges of the world don't leak, and neither do the edges of the region, which is all at about the baseline
+                    if x > xs.0 { flow += self.values[i - h * d] - value; }
+                    if x < xs.1 { flow += self.values[i + h * d] - value; }
+                    if y > ys.0 { flow += self.values[i - d] - value; }
+                    if y < ys.1 { flow += self.values[i + d] - value; }
+                    if z > zs.0 { flow += self.values[i - 1] - value; }
+                    if z < zs.1 { flow += self.values[i + 1] - value; }
+                    let diffused = value + self.diffusion * flow;
+                    let decayed = diffused + (self.baseline - diffused) * self.decay;
+                    if (decayed - self.baseline).abs() > FIELD_EPSILON {
+                        self.scratch[i] = decayed;
+                        self.mark_active(x, y, z);
+                    } else if self.decay > 0.0 {
+                        self.scratch[i] = self.baseline;
+                    } else {
+                        self.scratch[i] = decayed; // a field that doesn't decay keeps what spread thin, so nothing is lost
+                    }
+                }
+            }
+        }
+        for x in xs.0..=xs.1 {
+            for y in ys.0..=ys.1 {
+                let row = (x * h + y) * d;
+                self.values[row + zs.0..=row + zs.1].copy_from_slice(&self.scratch[row + zs.0..=row + zs.1]);
+            }
+        }
+    }
+    pub fn total(&self) -> f32 {
+        self.values.iter().sum()
+    }
+    pub fn pack(&self) -> PackedField {
+        let mut bounds: Option<[(usize, usize); 3]> = None;
+        for (i, &value) in self.values.iter().enumerate() {
+            if value != self.baseline {
+                let (x, y, z) = (i / (self.height * self.depth), i / self.depth % self.height, i % self.depth);
+                bounds = Some(match bounds {
+                    Some([xs, ys, zs]) => [(xs.0.min(x), xs.1.max(x)), (ys.0.min(y), ys.1.max(y)), (zs.0.min(z), zs.1.max(z))],
+                    None => [(x, x), (y, y), (z, z)],
+                });
+            }
+        }
+        let mut values = Vec::new();
+        if let Some([xs, ys, zs]) = bounds {
+            for x in xs.0..=xs.1 {
+                for y in ys.0..=ys.1 {
+                    let row = (x * self.height + y) * self.depth;
+                    values.extend_from_slice(&self.values[row + zs.0..=row + zs.1]);
+                }
+            }
+        }
+        PackedField { field: self.clone_empty(), bounds, values }
+    }
+    fn clone_empty(&self) -> Field { // everything but the values
+        Field {
+            values: Vec::new(),
+            scratch: Vec::new(),
+            active: self.active,
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            diffusion: self.diffusion,
+            decay: self.decay,
+            baseline: self.baseline,
+        }
+    }
+}
+
+// a copy of a field that only keeps the box of values away from the baseline, which is usually a small part of it
+#[derive(Clone)]
+pub struct PackedField {
+    field: Field,                         // with no values
+    bounds: Option<[(usize, usize); 3]>,  // inclusive x, y, z ranges outside of which everything is exactly the baseline
+    values: Vec<f32>,                     // the ones inside bounds, x-major
+}
+
+impl PackedField {
+    pub fn unpack(&self) -> Field {
+        let mut field = self.field.clone_empty();
+        field.values = vec![field.baseline; field.width * field.height * field.depth];
+        if let Some([xs, ys, zs]) = self.bounds {
+            let mut rows = self.values.chunks(zs.1 - zs.0 + 1);
+            for x in xs.0..=xs.1 {
+                for y in ys.0..=ys.1 {
+                    let row = (x * field.height + y) * field.depth;
+                    field.values[row + zs.0..=row + zs.1].copy_from_slice(rows.next().unwrap());
+                }
+            }
+        }
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nutrients_are_conserved() {
+        let (diffusion, decay, baseline) = NUTRIENT_PARAMS;
+        let mut field = Field::new(16, 16, 16, diffusion, decay, baseline);
+        field.add(8, 8, 8, 1.0);
+        for _ in 0..2000 {
+            field.update();
+        }
+        assert!((field.total() - 1.0).abs() < 1e-4, "total is {}", field.total());
+    }
+
+    #[test]
+    fn packing_keeps_every_value() {
+        let (diffusion, decay, baseline) = TEMPERATURE_PARAMS;
+        let mut field = Field::new(16, 12, 8, diffusion, decay, baseline);
+        field.add(3, 4, 5, 2.0);
+        field.add(10, 2, 1, -0.4);
+        for _ in 0..20 {
+            field.update();
+        }
+        let unpacked = field.pack().unpack();
+        assert_eq!(unpacked.values(), field.values());
+        assert_eq!(unpacked.active, field.active);
+        assert!(Field::new(4, 4, 4, diffusion, decay, baseline).pack().values.is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+// line graphs of recent history, drawn over the viewer
+
+use std::rc::Rc;
+use kiss3d::nalgebra::{Point2, Point3};
+use kiss3d::text::Font;
+use kiss3d::window::Window;
+use crate::cell::CellType;
+use crate::stats::{History, Stats};
+
+static GRAPH_WINDOW: usize = 500; // ticks of history kept and shown
+static GRAPH_SIZE: (f32, f32) = (360.0, 100.0); // logical pixels
+static GRAPH_MARGIN: f32 = 10.0;
+static GRAPH_TEXT_SIZE: f32 = 30.0;
+
+struct Series {
+    color: (f32, f32, f32),
+    value: Box<dyn Fn(&Stats) -> f32>,
+}
+
+struct Graph {
+    title: &'static str,
+    fixed_max: Option<f32>, // None scales to the largest value shown
+    series: Vec<Series>,
+}
+
+pub struct Graphs {
+    pub visible: bool,
+    history: History,
+    graphs: Vec<Graph>,
+    font: Rc<Font>,
+}
+
+fn series(color: (f32, f32, f32), value: impl Fn(&Stats) -> f32 + 'static) -> Series {
+    Series { color, value: Box::new(value) }
+}
+
+impl Default for Graphs {
+    fn default() -> Graphs {
+        Graphs::new()
+    }
+}
+
+impl Graphs {
+    pub fn new() -> Graphs {
+        let cell_series = CellType::all().into_iter().map(|cell_type| {
+            let i = cell_type.index();
+            series(cell_type.color(), move |stats| stats.cells[i] as f32)
+        }).collect();
+        Graphs {
+            visible: false,
+            history: History::new(GRAPH_WINDOW),
+            graphs: vec![
+                Graph { title: "organisms (white) and food (green)", fixed_max: None, series: vec![
+                    series((1.0, 1.0, 1.0), |stats| stats.population as f32),
+                    series((0.3, 0.8, 0.3), |stats| stats.food as f32),
+                ] },
+                Graph { title: "cells by type", fixed_max: None, series: cell_series },
+                Graph { title: "mean aggression (red) and hunger (blue)", fixed_max: Some(1.0), series: vec![
+                    series((1.0, 0.3, 0.3), |stats| stats.mean_aggression),
+                    series((0.3, 0.5, 1.0), |stats| stats.mean_hunger),
+                ] },
+            ],
+            font: Font::default(),
+        }
+    }
+
+    pub fn record(&mut self, stats: &Stats) { // call every frame, hidden or not, so the history is there when shown
+        self.history.record(stats);
+    }
+
+    pub fn draw(&self, window: &mut Window) { // stacked in the bottom right corner
+        if !self.visible {
+            return;
+        }
+        // planar coordinates are logical pixels from the middle, y up; text is in half physical pixels from the top left, y down
+        let scale = window.scale_factor() as f32;
+        let (half_width, half_height) = (window.width() as f32 / scale / 2.0, window.height() as f32 / scale / 2.0);
+        let to_text = |point: Point2<f32>| Point2::new((point.x + half_width) * scale * 2.0, (half_height - point.y) * scale * 2.0);
+        let (width, height) = GRAPH_SIZE;
+        let samples = self.history.samples();
+        for (i, graph) in self.graphs.iter().rev().enumerate() {
+            let left = half_width - GRAPH_MARGIN - width;
+            let bottom = -half_height + GRAPH_MARGIN + i as f32 * (height + GRAPH_MARGIN * 3.0);
+            let corners = [Point2::new(left, bottom), Point2::new(left + width, bottom), Point2::new(left + width, bottom + height), Point2::new(left, bottom + height)];
+            for c in 0..4 {
+                window.draw_planar_line(&corners[c], &corners[(c + 1) % 4], &Point3::new(0.4, 0.4, 0.4));
+            }
+            let max = graph.fixed_max.unwrap_or_else(|| {
+                samples.iter().flat_map(|stats| graph.series.iter().map(move |series| (series.value)(stats))).fold(1.0, f32::max)
+            });
+            window.draw_text(&format!("{} (max {})", graph.title, max), &to_text(Point2::new(left, bottom + height + GRAPH_MARGIN * 2.5)),
+                             GRAPH_TEXT_SIZE, &self.font, &Point3::new(1.0, 1.0, 1.0));
+            if samples.len() < 2 {
+                continue;
+            }
+            let step = width / (GRAPH_WINDOW - 1) as f32;
+            let start = left + width - (samples.len() - 1) as f32 * step; // the newest sample sits on the right edge
+            for series in &graph.series {
+                let (r, g, b) = series.color;
+                let point = |(j, stats): (usize, &Stats)| Point2::new(start + j as f32 * step, bottom + ((series.value)(stats) / max).min(1.0) * height);
+                let points: Vec<Point2<f32>> = samples.iter().enumerate().map(point).collect();
+                for pair in points.windows(2) {
+                    window.draw_planar_line(&pair[0], &pair[1], &Point3::new(r, g, b));
+                }
+            }
+        }
+    }
+}
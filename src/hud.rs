@@ -0,0 +1,130 @@
+// on-screen text with frame and simulation numbers
+
+use std::rc::Rc;
+use kiss3d::nalgebra::{Point2, Point3};
+use kiss3d::text::Font;
+use kiss3d::window::Window;
+use crate::cell::CellType;
+use crate::organism::direction_of;
+use crate::population::Population;
+use crate::snapshot::Snapshot;
+use crate::spatial::{FoodIndex, Surroundings};
+use crate::stats::{Rate, Stats, CELL_TYPE_COUNT};
+
+static HUD_TEXT_SIZE: f32 = 40.0;
+static INSPECTOR_WIDTH: f32 = 900.0;
+static NEAREST_FOOD_RANGE: i32 = 16; // how far the inspector looks for the nearest food
+
+struct Around { // what the inspector senses through, built once per snapshot rather than every frame
+    snapshot: (u64, u64), // tick and revision of the snapshot it was built from
+    population: Population,
+    food: FoodIndex,
+}
+
+pub struct Hud {
+    font: Rc<Font>,
+    around: Option<Around>,
+    frames: u64,
+    frame_rate: Rate,
+    tick_rate: Rate,
+    fps: f32,
+    ticks_per_second: f32,
+}
+
+impl Default for Hud {
+    fn default() -> Hud {
+        Hud::new()
+    }
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            font: Font::default(),
+            around: None,
+            frames: 0,
+            frame_rate: Rate::new(),
+            tick_rate: Rate::new(),
+            fps: 0.0,
+            ticks_per_second: 0.0,
+        }
+    }
+
+    pub fn frame(&mut self, tick: u64) { // call once per rendered frame
+        self.frames += 1;
+        self.fps = self.frame_rate.update(self.frames);
+        self.ticks_per_second = self.tick_rate.update(tick);
+    }
+
+    pub fn draw(&self, window: &mut Window, stats: &Stats, status: &[String]) { // status: extra lines from the viewer, like the camera mode
+        let mut lines = vec![
+            format!("fps {:.0}", self.fps),
+            format!("tick {} ({:.1}/s)", stats.tick, self.ticks_per_second),
+        ];
+        lines.extend_from_slice(status);
+        lines.push(format!("organisms {}", stats.population));
+        lines.push(format!("food {}  walls {}", stats.food, stats.walls));
+        for cell_type in CellType::all() {
+            lines.push(format!("{} {}", cell_type.name(), stats.cells[cell_type.index()]));
+        }
+        self.draw_lines(window, &lines, 10.0);
+    }
+
+    pub fn draw_inspector(&mut self, window: &mut Window, sim: &Snapshot, id: u64) { // details of one organism, down the right side
+        let Some(organism) = sim.organisms.iter().find(|organism| organism.id == id) else {
+            self.draw_lines(window, &[format!("organism #{} is gone", id)], self.right_column(window));
+            return;
+        };
+        let mut lines = vec![
+            format!("organism #{}", organism.id),
+            match organism.parent {
+                Some(parent) => format!("generation {}, parent #{}", organism.generation, parent),
+                None => format!("generation {}, placed", organism.generation),
+            },
+            format!("age {}  lifespan {}", organism.age, organism.lifespan),
+            format!("energy {}  health {}", organism.energy, organism.health),
+            format!("at {}, {}, {}", organism.x, organism.y, organism.z),
+        ];
+        let mut cells = [0; CELL_TYPE_COUNT];
+        for cell in &organism.cells {
+            cells[cell.cell_type.index()] += 1;
+        }
+        let composition: Vec<String> = CellType::all().iter()
+            .filter(|cell_type| cells[cell_type.index()] > 0)
+            .map(|cell_type| format!("{} {}", cells[cell_type.index()], cell_type.name()))
+            .collect();
+        lines.push(format!("cells {}", composition.join(", ")));
+        if let (Some(aggression), Some(hunger)) = (organism.get_aggression(), organism.get_hunger()) {
+            lines.push(format!("aggression {:.2}  hunger {:.2}", aggression, hunger));
+        }
+        if self.around.as_ref().is_none_or(|around| around.snapshot != (sim.tick, sim.revision)) {
+            let (population, food) = (sim.organisms.iter().cloned().collect(), FoodIndex::of(&sim.blocks));
+            self.around = Some(Around { snapshot: (sim.tick, sim.revision), population, food });
+        }
+        let cached = self.around.as_ref().unwrap();
+        let around = Surroundings { population: &cached.population, food: &cached.food };
+        match around.nearest_food(organism.x, organism.y, organism.z, NEAREST_FOOD_RANGE) {
+            Some((x, y, z)) => lines.push(format!("nearest food at {}, {}, {}", x, y, z)),
+            None => lines.push(format!("no food within {}", NEAREST_FOOD_RANGE)),
+        }
+        for eye in organism.cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Eye(_))) {
+            let decision = organism.decide(&around, eye);
+            let action = if decision.score < -0.5 { "flee" } else if decision.score > 0.5 { "advance" } else { "wander" };
+            lines.push(format!("eye {:?}: food {} danger {} killers {} -> {:.2} {}",
+                direction_of(eye.rotation), decision.food_in_sight, decision.danger_in_sight, decision.killers, decision.score, action));
+        }
+        self.draw_lines(window, &lines, self.right_column(window));
+    }
+
+    fn right_column(&self, window: &Window) -> f32 {
+        window.width() as f32 * 2.0 - INSPECTOR_WIDTH // text coordinates span twice the window width
+    }
+
+    fn draw_lines(&self, window: &mut Window, lines: &[String], x: f32) {
+        let color = Point3::new(1.0, 1.0, 1.0);
+        for (i, line) in lines.iter().enumerate() {
+            let position = Point2::new(x, 10.0 + i as f32 * HUD_TEXT_SIZE);
+            window.draw_text(line, &position, HUD_TEXT_SIZE, &self.font, &color);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// the simulation and its frontends, shared by the binary and the benchmarks
+
+pub mod organism;
+pub mod cell;
+pub mod world;
+pub mod block;
+pub mod field;
+pub mod terrain;
+pub mod vox;
+pub mod definition;
+pub mod simulation;
+pub mod population;
+pub mod spatial;
+pub mod snapshot;
+pub mod render;
+pub mod stats;
+pub mod hud;
+pub mod camera;
+pub mod pick;
+pub mod runner;
+pub mod rewind;
+pub mod events;
+pub mod raster;
+pub mod slice;
+pub mod tui;
+pub mod graphs;
@@ -1,26 +1,26 @@
 // main
-
-use std::time::{Instant};
-use kiss3d::scene::SceneNode;
 use rand::Rng;
-use kiss3d::nalgebra::{Point, Point2, Point3, Translation3, UnitQuaternion, Vector3};
+use std::fs;
 use kiss3d::window::Window;
 use kiss3d::light::Light;
-use kiss3d::camera::FirstPerson;
-use kiss3d::text::TextRenderer;
-mod organism;
-mod cell;
-mod world;
-mod block;
-use organism::*;
-use cell::*;
-use world::*;
-use world::*;
-use block::*;
-use std::sync::{Arc, Mutex};
-use std::sync::mpsc::channel;
-use std::time::Duration;
-use std::thread;
+use kiss3d::event::{Action, Key, Modifiers, MouseButton, WindowEvent};
+use kiss3d::nalgebra::{Point2, Vector2};
+use life_3d::{pick, tui, vox};
+use life_3d::events::{EventLog, Replay};
+use life_3d::snapshot::Snapshot;
+use life_3d::organism::*;
+use life_3d::world::*;
+use life_3d::terrain::*;
+use life_3d::definition::*;
+use life_3d::simulation::*;
+use life_3d::render::*;
+use life_3d::stats::*;
+use life_3d::hud::*;
+use life_3d::camera::*;
+use life_3d::runner::*;
+use life_3d::raster::*;
+use life_3d::slice::*;
+use life_3d::graphs::*;
 
 /*
 TODO:
@@ -30,208 +30,235 @@ TODO:
 - [x] make camera rotate around the simulation
 */
 
-// 1 in x chances
-static CHANCE_OF_REPRODUCTION: i8 = 20;             // how likely an organism is to reproduce
-static CHANCE_OF_MUTATION: i8 = 100;                // random mutation apart from reproduction
-static CHANCE_OF_FOOD_PRODUCTION: i8 = 30;          // chance of a producer cell producing food
+// terrain generated at startup
+static TERRAIN_KIND: TerrainKind = TerrainKind::Empty;
+static TERRAIN_SEED: u64 = 42;
+static SIMULATION_SEED: u64 = 42;             // everything random the organisms do; a seed replays the same run
+static ARENA_VOX: Option<&str> = None;              // a MagicaVoxel model to use as terrain instead, e.g. Some("arena.vox")
 
-static MAX_ORGANISMS: usize = 100;                  // soft limit; multiple organisms can be created in a single tick
-static MAX_BLOCKS: usize = 100;                     // this can by bypassed when an organism dies
+// the first organism: a preset name (blob, mover, plant, grazer, hunter), or a path to a .ron definition or .vox model
+static STARTER_ORGANISM: &str = "blob";
 
-fn update_world(organisms: &mut Vec<Organism>, new_organisms: &mut Vec<Organism>, blocks: &mut Vec<Block>, max_organisms: usize, max_blocks: usize, sim_world: &mut World) {
-    let organisms_len = organisms.len();    
+// a file of timed commands to run, one per line, e.g. "100 meteor 0 0 0 8"; see Command::parse
+static SCRIPT: Option<&str> = None;
 
-    for organism in organisms.iter_mut() {
+// pictures of every nth tick saved to a directory by the software renderer, e.g. Some(("frames", 10))
+static RECORD_FRAMES: Option<(&str, u64)> = None;
 
-        // reproduce
-        if rand::thread_rng().gen_range(0..(CHANCE_OF_REPRODUCTION)) == 0 {
-            if organisms_len < max_organisms {
-                let mut new_organism = organism.reproduce();
-                if rand::thread_rng().gen_range(0..2) == 0 {
-                    new_organism.mutate(); // reproduced organisms have a 50% chance of mutation
-                }
-                new_organisms.push(new_organism);
-            }
-        }
-        // produce food
-        if rand::thread_rng().gen_range(0..CHANCE_OF_FOOD_PRODUCTION) == 0 {
-            if organism.cells.iter().any(|cell| matches!(cell.cell_type, CellType::Producer(_))) {
-                if max_blocks > blocks.len() {
-                    if let Some(block) = organism.produce_food() {
-                        blocks.push(block);
-                    }
-                }
-            }
-        }
-        // random mutation
-        if rand::thread_rng().gen_range(0..CHANCE_OF_MUTATION) == 0 {
-            organism.mutate();
-        }
+// everything that happens written to a file, to watch again later with --replay <file>, e.g. Some("run.events")
+static EVENT_LOG: Option<&str> = None;
 
-        // Eats one food block if adjacent to one and has an eater cell
-        if organism.cells.iter().any(|cell| matches!(cell.cell_type, CellType::Eater)) {
-            organism.eat(&mut *blocks);
-        }
+static CLICK_TOLERANCE: f64 = 4.0;  // pixels the mouse can move between press and release and still select
 
-        // Housekeeping
-        if organism.lifespan > 0 {
-            organism.lifespan -= 1;
-        }
-        if organism.energy > 0 {
-            organism.energy -= 2;
-        }
-        if organism.is_dead() {
-            // println!("Organism died");
-            for val in organism.kill() {
-                if blocks.len() < max_blocks {
-                    blocks.push(val);       // Add the dead organism's cells as food blocks
-                }
-            }
+fn load_starter(spec: &str) -> Organism {
+    if spec.ends_with(".vox") {
+        vox::load_organism(spec).expect("couldn't load organism model")
+    } else if spec.ends_with(".ron") {
+        OrganismDefinition::load(spec).expect("couldn't load organism definition").to_organism()
+    } else {
+        match OrganismDefinition::preset(spec) {
+            Some(definition) => definition.to_organism(),
+            None => panic!("unknown preset {}, expected one of {:?}", spec, OrganismDefinition::preset_names()),
         }
     }
+}
 
-    let organisms_clone = &(organisms.clone()); // avoids borrowing issues; maybe there's a better way though
-            // damage nearby organisms if there are killer cells
-    let mut to_damage = Vec::new();
-    for (i, organism) in organisms.iter_mut().enumerate() {
-        if organism.cells.iter().any(|cell| matches!(cell.cell_type, CellType::Killer)) {
-            to_damage.push(i);
-        }
-    }
-    for i in to_damage {
-        organisms_clone[i].damage_nearby_organisms(organisms);
-    }
-    let mut to_move_better = Vec::new();
-    for (i, organism) in organisms.iter_mut().enumerate() {
-        if organism.cells.iter().any(|cell| matches!(cell.cell_type, CellType::Mover)) {
-            if organism.cells.iter().any(|cell| matches!(cell.cell_type, CellType::Eye(_))) {
-                to_move_better.push(i);
-            }
-            else { organism.teleport_random(); }
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let runner = match args.iter().position(|arg| arg == "--replay") {
+        Some(i) => {
+            let path = args.get(i + 1).expect("--replay needs an event log");
+            Runner::spawn_replay(Replay::load(path).expect("couldn't load event log"))
         }
+        None => start_simulation(),
+    };
+    if args.iter().any(|arg| arg == "--tui") { // text in the terminal instead of a window
+        tui::run(&runner).expect("terminal frontend failed");
+    } else {
+        run_viewer(&runner);
     }
-    for i in to_move_better {
-        organisms[i].move_better(organisms_clone, blocks);
-    }
-    organisms.retain(|organism| { // Remove dead organisms
-        let result = !organism.is_dead();
-        if !result { // println!("Removing dead organism");
-        } result
-    });
-
-    // println!("Number of organisms: {}", organisms.len());
-    // println!("Number of blocks: {}", blocks.len());
 }
 
-fn main() {
-    let (tx, rx) = channel();
-
-    let mut sim_world = World::new(128, 128, 128);
-
-    let organisms = Arc::new(Mutex::new(vec![Organism::new()])); // Create a vec with one new organism
-    let blocks = Arc::new(Mutex::new(vec![]));
+fn start_simulation() -> Runner {
+    let sim_world = World::new(128, 128, 128);
 
-    let organisms_clone = Arc::clone(&organisms);
-    let blocks_clone = Arc::clone(&blocks);
+    let walls = match ARENA_VOX {
+        Some(path) => vox::load_terrain(path).expect("couldn't load arena"),
+        None => generate(&TerrainParams::new(TERRAIN_KIND, TERRAIN_SEED)),
+    };
+    let first_organism = load_starter(STARTER_ORGANISM);
 
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(200)); // Sleep
-
-            let mut new_organisms = Vec::new();
-            let mut new_blocks = Vec::new();
-
-            update_world(&mut *organisms_clone.lock().unwrap(), &mut new_organisms, &mut *blocks_clone.lock().unwrap(), MAX_ORGANISMS, MAX_BLOCKS, &mut sim_world);
-
-            tx.send((new_organisms, new_blocks)).unwrap();
+    let mut sim = Simulation::new(sim_world, vec![first_organism], walls, SIMULATION_SEED);
+    if let Some(path) = SCRIPT {
+        let text = fs::read_to_string(path).expect("couldn't read script");
+        for (tick, command) in Command::parse_script(&text).expect("couldn't parse script") {
+            sim.schedule(tick, command);
         }
+    }
+    let mut recorder = RECORD_FRAMES.map(|(directory, every)| {
+        FrameRecorder::new(directory, every, RasterView::new(640, 480)).expect("couldn't create frame directory")
     });
+    let mut log = EVENT_LOG.map(|path| {
+        sim.record_events();
+        EventLog::create(path, &Snapshot::of(&sim)).expect("couldn't create event log")
+    });
+    Runner::spawn_with_observer(sim, move |snapshot, events| {
+        if let Some(recorder) = &mut recorder {
+            if let Err(e) = recorder.record(snapshot) {
+                println!("Couldn't record frame: {}", e);
+            }
+        }
+        if let Some(log) = &mut log {
+            if let Err(e) = log.write(snapshot.tick, events) {
+                println!("Couldn't write to the event log: {}", e);
+            }
+        }
+    })
+}
 
+fn run_viewer(runner: &Runner) {
     let mut window = Window::new("Main Window");
     window.set_light(Light::StickToCamera);
     window.set_framerate_limit(Some(60));
-    let mut camera = FirstPerson::new_with_frustrum(90.0, 0.1, 200.0, Point3::new(0.0, 0.0, 0.0).into(), Point3::new(0.0, 0.0, 0.0).into());
-    camera.set_move_step(5.0);
-    camera.set_pitch_step(0.01);
-    camera.set_yaw_step(0.01);
-    //move_dir takes up bool, down, left, right
-    camera.rebind_up_key(Some(kiss3d::event::Key::W));
-    let x = camera.up_key();
-    println!("Camera up key: {:?}", x);
-    let mut parent_objects = Vec::new();
-    let mut last_instant = Instant::now(); // for fps calculation
-
-    let mut frame_counter = 0;
-    while window.render() {                                                     // For each frame
-        frame_counter += 1;
-        for mut parent in parent_objects.drain(..) { // delete old render objects
-            window.remove_node(&mut parent);
-        }
-        
-
-        if let Ok((new_organisms, new_blocks)) = rx.try_recv() {
-            organisms.lock().unwrap().append(&mut new_organisms.clone());
-            blocks.lock().unwrap().append(&mut new_blocks.clone());
-        }
+    let mut camera = CameraRig::new();
+    let mut renderer = Renderer::new();
+    let mut hud = Hud::new();
+    let mut slice_panel = SlicePanel::new();
+    let mut graphs = Graphs::new();
+    let mut snapshot_counter = 0;
+    let mut selected: Option<u64> = None; // organism shown in the inspector
+    let mut press_position = None;        // where the left button went down, to tell clicks from camera drags
 
+    while window.render_with_camera(camera.camera()) {                          // For each frame
 
-        let num_organisms = organisms.lock().unwrap().len();
-
-        for organism in &mut *organisms.lock().unwrap() {
-            let mut parent = window.add_group();
-            for cell in &organism.cells { // render cells
-                let mut cube = parent.add_cube(1.0, 1.0, 1.0);
-                match cell.cell_type {
-                    CellType::Brain(_) => cube.set_color(0.9, 0.2, 0.4),
-                    CellType::Eye(_) => {
-                        cube.set_color(1.0, 1.0, 1.0);
-                        // a is the point of the eye: organism.x + cell.local_x, organism.y + cell.local_y, organism.z + cell.local_z
-                        let a = Point3::new((organism.x + cell.local_x) as f32, (organism.y + cell.local_y) as f32, (organism.z + cell.local_z) as f32);
-                        let offset = match cell.rotation {
-                            0 => Vector3::new(1.0, 0.0, 0.0), // x
-                            1 => Vector3::new(-1.0, 0.0, 0.0), // -x
-                            2 => Vector3::new(0.0, 1.0, 0.0), // y
-                            3 => Vector3::new(0.0, -1.0, 0.0), // -y
-                            4 => Vector3::new(0.0, 0.0, 1.0), // z
-                            _ => Vector3::new(0.0, 0.0, -1.0), // -z
-                        };
-                        let b = a + offset;
-                        window.draw_line(&a, &b, &Point3::new(1.0, 1.0, 1.0));
-                    },
-                    CellType::Armor => cube.set_color(1.0, 1.0, 0.0),
-                    CellType::Killer => cube.set_color(1.0, 0.0, 0.0),      // killers are red
-                    CellType::Eater => cube.set_color(1.0, 0.0, 1.0),       // eaters are purple
-                    CellType::Mover => cube.set_color(0.0, 0.0, 1.0),       // movers are blue
-                    CellType::Producer(_) => cube.set_color(0.0, 1.0, 0.0), // producers are green
-                                                                            // eyes are white
-                };
-                cube.append_translation(&Translation3::new((organism.x + cell.local_x) as f32, (organism.y + cell.local_y) as f32, (organism.z + cell.local_z) as f32));
+        for event in window.events().iter() {
+            if let WindowEvent::MouseButton(MouseButton::Button1, action, _) = event.value {
+                let cursor = window.cursor_pos();
+                match action {
+                    Action::Press => press_position = cursor,
+                    Action::Release => if let (Some((x, y)), Some((px, py))) = (cursor, press_position) {
+                        if (x - px).abs() + (y - py).abs() < CLICK_TOLERANCE {
+                            let size = Vector2::new(window.width() as f32, window.height() as f32);
+                            let (origin, direction) = camera.camera().unproject(&Point2::new(x as f32, y as f32), &size);
+                            selected = pick::pick(&runner.snapshot(), origin, direction);
+                        }
+                    }
+                }
             }
-            for block in &*blocks.lock().unwrap() {
-                let mut cube = parent.add_cube(1.0, 1.0, 1.0);
-                match block.block_type {
-                    BlockType::Food => cube.set_color(0.2, 0.3, 0.3),
-                    BlockType::Wall => cube.set_color(0.6, 0.6, 0.6),
-                };
-                cube.append_translation(&Translation3::new(block.x as f32, block.y as f32, block.z as f32));
+            if let WindowEvent::Key(key, Action::Press, modifiers) = event.value {
+                let sim = runner.snapshot();
+                let mut rng = rand::thread_rng();
+                let mut random_position = || (rng.gen_range(-32..32), rng.gen_range(-32..32), rng.gen_range(-32..32));
+                match key {
+                    Key::V if modifiers.contains(Modifiers::Shift) && selected.is_some() => { // export just the selected organism
+                        match sim.organisms.iter().find(|organism| Some(organism.id) == selected) {
+                            Some(organism) => {
+                                let path = format!("organism_{}.vox", organism.id);
+                                match vox::save_organism(&path, organism) {
+                                    Ok(()) => println!("Saved {}", path),
+                                    Err(e) => println!("Couldn't save {}: {}", path, e),
+                                }
+                            }
+                            None => println!("The selected organism is gone"),
+                        }
+                    }
+                    Key::V => { // export the world for inspection in MagicaVoxel
+                        let path = format!("snapshot_{}.vox", snapshot_counter);
+                        match vox::save_world(&path, &sim.organisms, &sim.blocks) {
+                            Ok(()) => println!("Saved {}", path),
+                            Err(e) => println!("Couldn't save {}: {}", path, e),
+                        }
+                        snapshot_counter += 1;
+                    }
+                    Key::P => { // a picture from the current camera, drawn in software
+                        let (eye, at) = camera.eye_and_at();
+                        let mut view = RasterView::new(window.width(), window.height());
+                        view.eye = [eye.x, eye.y, eye.z];
+                        view.at = [at.x, at.y, at.z];
+                        let path = format!("render_{}.png", sim.tick);
+                        match render(&sim, &view).save_png(&path) {
+                            Ok(()) => println!("Saved {}", path),
+                            Err(e) => println!("Couldn't save {}: {}", path, e),
+                        }
+                    }
+                    Key::N => { // spawn a random organism somewhere
+                        let (x, y, z) = random_position();
+                        runner.apply(Command::Spawn { definition: None, x, y, z });
+                    }
+                    Key::M => {
+                        let (x, y, z) = random_position();
+                        runner.apply(Command::Meteor { x, y, z, radius: 8 });
+                    }
+                    Key::C => runner.apply(Command::Cull { fraction: 0.5 }),
+                    Key::O => camera.set_mode(CameraMode::Orbit),
+                    Key::F => { // follows the selected organism, again to move on to the next one
+                        match selected {
+                            Some(id) if camera.target != Some(id) || camera.mode != CameraMode::Follow => camera.follow(id),
+                            _ => camera.follow_next(&sim),
+                        }
+                        selected = camera.target;
+                    }
+                    Key::X => selected = None,
+                    Key::Space => runner.toggle_pause(),
+                    Key::Period if modifiers.contains(Modifiers::Shift) => runner.rewind_by(10),
+                    Key::Period => runner.step(), // forward through the past while rewound
+                    Key::Comma => runner.rewind_by(if modifiers.contains(Modifiers::Shift) { -10 } else { -1 }),
+                    Key::B => runner.fork(), // carry on from the tick rewound to
+                    Key::Equals | Key::Add => runner.faster(),
+                    Key::Minus | Key::Subtract => runner.slower(),
+                    Key::H => slice_panel.visible = !slice_panel.visible,
+                    Key::J => slice_panel.axis = slice_panel.axis.next(),
+                    Key::PageUp => slice_panel.move_by(if modifiers.contains(Modifiers::Shift) { 8 } else { 1 }),
+                    Key::PageDown => slice_panel.move_by(if modifiers.contains(Modifiers::Shift) { -8 } else { -1 }),
+                    Key::Y => { // the current slice as a picture and as text
+                        let slice = Slice::take(&sim, slice_panel.axis, slice_panel.position);
+                        let name = format!("slice_{}_{}", slice_panel.describe().replace(" = ", ""), sim.tick);
+                        let saved = slice.to_image(4).save_png(&format!("{}.png", name))
+                            .and_then(|()| fs::write(format!("{}.txt", name), slice.to_ascii()));
+                        match saved {
+                            Ok(()) => println!("Saved {}.png and {}.txt", name, name),
+                            Err(e) => println!("Couldn't save {}: {}", name, e),
+                        }
+                    }
+                    Key::U => graphs.visible = !graphs.visible,
+                    Key::K => renderer.set_color_mode(renderer.color_mode().next()),
+                    Key::L => renderer.set_xray_walls(!renderer.xray_walls()),
+                    Key::G => camera.set_mode(CameraMode::Free),
+                    Key::R => camera.reset(),
+                    Key::T => camera.auto_rotate = !camera.auto_rotate,
+                    key => if let Some(slot) = bookmark_slot(key) { // shift to save, plain to recall
+                        if modifiers.contains(Modifiers::Shift) {
+                            camera.save_bookmark(slot);
+                        } else if !camera.load_bookmark(slot) {
+                            println!("No camera bookmark {}", slot + 1);
+                        }
+                    }
+                }
             }
-            parent_objects.push(parent);
         }
 
-        let mut fps_renderer = TextRenderer::new();
-
-        // Draw text
-        let font2 = kiss3d::text::Font::default();
-        let color2 = Point3::new(1.0, 1.0, 1.0);
-        let point2 = Point2::new(0.0, 0.0);
-        fps_renderer.draw_text("hi!", &point2, 10.0, &font2, &color2);
-        
-        // Render the text
-        fps_renderer.render(window.width() as f32, window.height() as f32);
-
-        let now = Instant::now();
-        let framerate = 1.0 / (now.duration_since(last_instant)).as_secs_f32();
-        //                                                                              println!("Current framerate: {}", framerate);
-        last_instant = now;
+        let sim = runner.snapshot(); // the newest published tick, the simulation carries on meanwhile
+        renderer.sync(&mut window, &sim);
+        renderer.draw_eyes(&mut window, &sim);
+        if let Some(id) = selected {
+            renderer.draw_selection(&mut window, &sim, id);
+            hud.draw_inspector(&mut window, &sim, id);
+        }
+        camera.update(&sim);
+        slice_panel.sync(&mut window, &sim);
+        slice_panel.draw_plane(&mut window, &sim);
+        let stats = Stats::collect(&sim);
+        drop(sim);
+
+        hud.frame(stats.tick);
+        graphs.record(&stats);
+        graphs.draw(&mut window);
+        let status = [
+            format!("speed {}", runner.describe()),
+            format!("camera {}", camera.describe()),
+            if slice_panel.visible { format!("slice {}", slice_panel.describe()) } else { "slice hidden".to_string() },
+            format!("color {:?}{}", renderer.color_mode(), if renderer.xray_walls() { ", x-ray walls" } else { "" }).to_lowercase(),
+        ];
+        hud.draw(&mut window, &stats, &status);
     }
 }
\ No newline at end of file
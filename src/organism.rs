@@ -1,272 +1,412 @@
 // defines the organisms in the world
 
-use core::num;
-
+use std::sync::atomic::{AtomicU64, Ordering};
 use rand::{Rng, prelude::IteratorRandom};
-// use octree_rs::Octree;
+use serde::{Deserialize, Serialize};
 use crate::cell::{Cell, CellType, Brain, Eye, Producer};
-use crate::world::{World, Entity};
-use crate::block::{Block, BlockType};
+use crate::block::{Block, BlockType, FoodSource};
+use crate::world::World;
+use crate::field::FieldKind;
+use crate::spatial::Surroundings;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+pub static REPRODUCTION_COST: u8 = 10; // energy a parent spends on each child
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub struct Decision { // what move_better sees through one eye, and what it makes of it
+    pub food_in_sight: usize,
+    pub danger_in_sight: usize, // killer cells of nearby organisms in sight
+    pub killers: usize,         // its own killer cells
+    pub hunger: f32,
+    pub aggression: f32,
+    pub score: f32,             // below -0.5 runs away, above 0.5 moves forward, otherwise wanders
+}
 
 #[derive(Clone)]
+#[derive(Serialize, Deserialize)]
 pub struct Organism { // an organism is a collection of cells, including a brain.
+    pub id: u64, // unique for the whole run; clones keep it
     pub cells: Vec<Cell>, 
     pub health: u8,
     pub energy: u8,
     pub lifespan: u8,
+    pub age: u32,            // ticks lived
+    pub parent: Option<u64>, // id of the organism this one was born from
+    pub generation: u32,     // 0 for organisms that were placed rather than born
+    pub founder: u64,        // id of the placed organism at the root of its lineage
     pub x: i8,
     pub y: i8,
     pub z: i8,
 }
 impl Organism {
+    #[allow(clippy::new_without_default)] // every new organism takes the next id, which a default shouldn't
     pub fn new() -> Organism {
+        let mut organism = Organism::unnumbered();
+        organism.id = next_id();
+        organism.founder = organism.id;
+        organism
+    }
+    fn unnumbered() -> Organism { // id 0, for whoever adds it to number; the global counter would give ids in whatever order threads got there
         let brain = Brain {
             aggression: 0.5,
             hunger: 0.5,
         };
         let brain_cell = Cell::new(CellType::Brain(brain), 0, 0, 0, 0);
         Organism {
+            id: 0,
             // cells: vec![brain_cell, Cell::new(CellType::Mover, 0, 1, 1, 0)],
             cells: vec![brain_cell],
             health: 100,
             energy: 100,
             lifespan: 100,
+            age: 0,
+            parent: None,
+            generation: 0,
+            founder: 0,
             x: 0,
             y: 0,
             z: 0,
         }
     }
-    pub fn mutate(&mut self) { // mutates a random cell
-        let mut rng = rand::thread_rng();
-    
-        match rng.gen_range(0..3) {
-            0 => self.add_random_cell(),
-            1 => {
-                let cell_index = rng.gen_range(0..self.cells.len());
-                self.cells[cell_index].mutate();
-            },
-            2 => self.remove_random_cell(),
-            _ => (),
+    pub fn view(&self) -> OrganismView<'_> {
+        OrganismView {
+            id: self.id,
+            cells: &self.cells,
+            health: self.health,
+            energy: self.energy,
+            lifespan: self.lifespan,
+            age: self.age,
+            parent: self.parent,
+            generation: self.generation,
+            founder: self.founder,
+            x: self.x,
+            y: self.y,
+            z: self.z,
         }
     }
-    pub fn teleport_random(&mut self) {
-        let mut rng = rand::thread_rng();
-        let dx = rng.gen_range(-1..2);
-        let dy = rng.gen_range(-1..2);
-        let dz = rng.gen_range(-1..2);
-        let num_mover_cells = self.cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Mover)).count();
-        self.x += dx*num_mover_cells as i8;
-        self.y += dy*num_mover_cells as i8;
-        self.z += dz*num_mover_cells as i8;
-        // 50% chance to rotate
-        if rng.gen_range(0..2) == 0 {
-            self.rotate();
-            // println!("organism rotated :)")
-        }
-
+    pub fn mutate(&mut self, rng: &mut impl Rng) { // mutates a random cell
+        mutate_cells(&mut self.cells, rng);
+    }
+    pub fn teleport_random(&mut self, rng: &mut impl Rng) {
+        let mut position = (self.x, self.y, self.z);
+        wander(&mut position, &mut self.cells, rng);
+        (self.x, self.y, self.z) = position;
+    }
+    pub fn reproduce(&mut self, rng: &mut impl Rng) -> Organism {
+        self.energy = self.energy.saturating_sub(REPRODUCTION_COST);
+        self.offspring(rng)
+    }
+    pub fn offspring(&self, rng: &mut impl Rng) -> Organism { // a copy placed nearby, without paying for it
+        self.view().offspring(rng)
+    }
+    pub fn produce_food(&self, rng: &mut impl Rng) -> Option<Block> {
+        self.view().produce_food(rng)
+    }
+    pub fn add_random_cell(&mut self, rng: &mut impl Rng) {
+        add_random_cell(&mut self.cells, rng);
+    }
+    pub fn remove_random_cell(&mut self, rng: &mut impl Rng) { // removes a random cell, except the brain
+        remove_random_cell(&mut self.cells, rng);
+    }
+    pub fn shift(&mut self, dx: i8, dy: i8, dz: i8) {
+        self.x = shifted(self.x, dx as i32);
+        self.y = shifted(self.y, dy as i32);
+        self.z = shifted(self.z, dz as i32);
+    }
+    pub fn eat(&mut self, block: &Block) { // gains the food's remaining nutrition, unless already full
+        self.energy = block.feed(self.energy);
+    }
+    pub fn is_dead(&self) -> bool {
+        self.view().is_dead()
+    }
+    pub fn kill(&self) -> Vec<Block> {
+        self.view().kill()
+    }
+    pub fn get_nearby_blocks<'a>(&self, blocks: &'a [Block]) -> Vec<&'a Block> {
+        let near = |a: i8, b: i8| (a as i32 - b as i32).abs() <= 1;
+        blocks.iter().filter(|block| near(self.x, block.x) && near(self.y, block.y) && near(self.z, block.z)).collect()
+    }
+    pub fn hits_wall(&self, world: &World) -> bool {
+        self.view().hits_wall(world)
+    }
+    pub fn sample_field(&self, world: &World, kind: FieldKind) -> f32 { // average of the field over all of the organism's cells
+        self.view().sample_field(world, kind)
+    }
+    pub fn get_hunger(&self) -> Option<f32> {
+        brain_of(&self.cells).map(|brain| brain.hunger)
+    }
+    pub fn get_aggression(&self) -> Option<f32> {
+        brain_of(&self.cells).map(|brain| brain.aggression)
+    }
+    pub fn decide(&self, around: &Surroundings, eye: &Cell) -> Decision {
+        self.view().decide(around, eye)
     }
-    pub fn reproduce(&mut self) -> Organism {
-        if self.energy >= 10 { self.energy -= 10; }
-        else { self.energy = 0; }
+    pub fn move_better(&mut self, around: &Surroundings, rng: &mut impl Rng) {
+        let mut position = (self.x, self.y, self.z);
+        move_better(&mut position, &mut self.cells, around, rng);
+        (self.x, self.y, self.z) = position;
+    }
+    pub fn rotate(&mut self, rng: &mut impl Rng) {
+        rotate_cells(&mut self.cells, rng);
+    }
+}
+
+// an organism as the rules see it, whether it's an Organism or a row of a Population
+#[derive(Clone, Copy)]
+pub struct OrganismView<'a> {
+    pub id: u64,
+    pub cells: &'a [Cell],
+    pub health: u8,
+    pub energy: u8,
+    pub lifespan: u8,
+    pub age: u32,
+    pub parent: Option<u64>,
+    pub generation: u32,
+    pub founder: u64,
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
+}
 
-        let mut new_organism = Organism::new();
-        let size = self.cells.len() as i8;
-        new_organism.cells = self.cells.clone();
-        new_organism.x = self.x + rand::thread_rng().gen_range((size*-1)*2 as i8 .. (size*2)+1 as i8); // random offset from parent is proportional to the size of the parent
-        new_organism.y = self.y + rand::thread_rng().gen_range((size*-1)*2 as i8 .. (size*2)+1 as i8);
-        new_organism.z = self.z + rand::thread_rng().gen_range((size*-1)*2 as i8 .. (size*2)+1 as i8);
+impl OrganismView<'_> {
+    pub fn to_organism(&self) -> Organism {
+        Organism {
+            id: self.id,
+            cells: self.cells.to_vec(),
+            health: self.health,
+            energy: self.energy,
+            lifespan: self.lifespan,
+            age: self.age,
+            parent: self.parent,
+            generation: self.generation,
+            founder: self.founder,
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        }
+    }
+    pub fn offspring(&self, rng: &mut impl Rng) -> Organism { // a copy placed nearby, without paying for it; the id is left at 0
+        let mut new_organism = Organism::unnumbered();
+        let reach = (self.cells.len() * 2).min(i8::MAX as usize) as i8; // random offset from parent is proportional to the size of the parent
+        new_organism.cells = self.cells.to_vec();
+        new_organism.parent = Some(self.id);
+        new_organism.generation = self.generation + 1;
+        new_organism.founder = self.founder;
+        new_organism.x = shifted(self.x, rng.gen_range(-reach..=reach) as i32);
+        new_organism.y = shifted(self.y, rng.gen_range(-reach..=reach) as i32);
+        new_organism.z = shifted(self.z, rng.gen_range(-reach..=reach) as i32);
         // println!("reproducing");
         new_organism
     }
-    pub fn produce_food(&mut self) -> Option<Block> {
-        let mut rng = rand::thread_rng();
-        for cell in &self.cells {
+    pub fn produce_food(&self, rng: &mut impl Rng) -> Option<Block> {
+        for cell in self.cells {
             if let CellType::Producer(_) = cell.cell_type {
                 let dx = rng.gen_range(-1..2);
                 let dy = rng.gen_range(-1..2);
                 let dz = rng.gen_range(-1..2);
                 // println!("Producing food");
-                return Some(Block::new(BlockType::Food, self.x + dx, self.y + dy, self.z + dz));
+                return Some(Block::new(BlockType::Food(FoodSource::Producer), shifted(self.x, dx), shifted(self.y, dy), shifted(self.z, dz)));
             }
         }
         None
     }
-    pub fn add_random_cell(&mut self) {
-        // println!("Adding a cell");
-        let mut rng = rand::thread_rng();
-        let cell_type = match rng.gen_range(0..7) { // add random rotation
-            0 => CellType::Eye(Eye {}),
-            1 => CellType::Armor,
-            2 => CellType::Killer,
-            3 => CellType::Eater,
-            4 => CellType::Producer(Producer {}),
-            5 => CellType::Mover,
-            _ => CellType::Eater,
-        };
-        let dx = rng.gen_range(-1..2);
-        let dy = rng.gen_range(-1..2);
-        let dz = rng.gen_range(-1..2);
-        let random_rotation = rng.gen_range(0..6);
-        self.cells.push(Cell::new(cell_type, random_rotation, dx, dy, dz));
-        // println!("An organism added a cell");
-    }
-    pub fn remove_random_cell(&mut self) { // removes a random cell, except the brain
-        let mut rng = rand::thread_rng();
-        if self.cells.len() > 1 {
-            let cell_index = rng.gen_range(0..self.cells.len());
-            if matches!(self.cells[cell_index].cell_type, CellType::Brain(_)) {
-                return;
-            }
-            self.cells.remove(cell_index);
-        }
-    }
-    pub fn shift(&mut self, dx: i8, dy: i8, dz: i8) {
-        self.x += dx;
-        self.y += dy;
-        self.z += dz;
-    }
-    pub fn eat(&mut self, blocks: &mut Vec<Block>) {
-        let nearby_blocks = self.get_nearby_blocks(blocks);
-        let mut to_remove = Vec::new();
-    
-        for (i, block) in nearby_blocks.iter().enumerate() {
-            if matches!(block.block_type, BlockType::Food) {
-                if self.energy < 100 {
-                    self.energy += 10;
-                }
-                to_remove.push(i);
-                // println!("Eating a block, gained energy: {}", self.energy);
-            }
-        }
-    
-        for i in to_remove.iter().rev() {
-            blocks.remove(*i);
-        }
-    }
     pub fn is_dead(&self) -> bool {
-        if self.health <= 0 || self.energy <= 0 || self.lifespan <= 0 {
-            true
-        } else {
-            false }
+        self.health == 0 || self.energy == 0 || self.lifespan == 0
     }
     pub fn kill(&self) -> Vec<Block> {
         let mut blocks = Vec::new();
-        for cell in &self.cells {
-            blocks.push(Block::new(BlockType::Food, self.x + cell.local_x, self.y + cell.local_y, self.z + cell.local_z));
+        for (x, y, z) in self.cells.iter().filter_map(|cell| cell_position((self.x, self.y, self.z), cell)) {
+            blocks.push(Block::new(BlockType::Food(FoodSource::Carcass), x, y, z));
         }
         blocks
     }
-    pub fn get_nearby_organisms<'a>(&self, organisms: &'a Vec<Organism>) -> Vec<&'a Organism> {
-        let mut nearby_organisms = Vec::new();
-        for organism in organisms {
-            if (self.x - organism.x).abs() <= 1 && 
-                (self.y - organism.y).abs() <= 1 && 
-                (self.z - organism.z).abs() <= 1 {
-                nearby_organisms.push(organism);
-            }
-        }
-        nearby_organisms
-    }
-    pub fn get_nearby_blocks<'a>(&self, blocks: &'a Vec<Block>) -> Vec<&'a Block> {
-        let mut nearby_blocks = Vec::new();
-        for block in blocks {
-            if (self.x - block.x).abs() <= 1 && 
-                (self.y - block.y).abs() <= 1 && 
-                (self.z - block.z).abs() <= 1 {
-                // println!("Block found at: {}, {}, {}; Organism at: {}, {}, {}", block.x, block.y, block.z, self.x, self.y, self.z);
-                nearby_blocks.push(block);
-            }
-        }
-        nearby_blocks
+    pub fn hits_wall(&self, world: &World) -> bool {
+        hits_wall((self.x, self.y, self.z), self.cells, world)
     }
-    pub fn get_hunger(&self) -> Option<f32> {
-        for cell in &self.cells {
-            if let CellType::Brain(brain) = &cell.cell_type {
-                return Some(brain.hunger);
-            }
-        }
-        None
+    pub fn sample_field(&self, world: &World, kind: FieldKind) -> f32 { // average of the field over all of the organism's cells
+        let total: f32 = self.cells.iter()
+            .map(|cell| match cell_position((self.x, self.y, self.z), cell) {
+                Some((x, y, z)) => world.sample_field(kind, x, y, z),
+                None => world.field(kind).baseline, // outside the field, like anything else off the grid
+            })
+            .sum();
+        total / self.cells.len() as f32
     }
-    pub fn get_aggression(&self) -> Option<f32> {
-        for cell in &self.cells {
-            if let CellType::Brain(brain) = &cell.cell_type {
-                return Some(brain.aggression);
-            }
+    pub fn decide(&self, around: &Surroundings, eye: &Cell) -> Decision {
+        decide((self.x, self.y, self.z), self.cells, around, eye)
+    }
+}
+
+// the rules below work on bare cells and positions, so an Organism and a Population row share them
+
+pub fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+fn shifted(v: i8, by: i32) -> i8 { // stops at the ends of what an i8 holds rather than wrapping round to the other side
+    (v as i32 + by).clamp(i8::MIN as i32, i8::MAX as i32) as i8
+}
+
+pub fn cell_position((x, y, z): (i8, i8, i8), cell: &Cell) -> Option<(i8, i8, i8)> { // none past the ends of an i8, which no world reaches
+    Some((x.checked_add(cell.local_x)?, y.checked_add(cell.local_y)?, z.checked_add(cell.local_z)?))
+}
+
+fn brain_of(cells: &[Cell]) -> Option<&Brain> {
+    cells.iter().find_map(|cell| match &cell.cell_type {
+        CellType::Brain(brain) => Some(brain),
+        _ => None,
+    })
+}
+
+pub fn mutate_cells(cells: &mut Vec<Cell>, rng: &mut impl Rng) { // mutates a random cell
+    match rng.gen_range(0..3) {
+        0 => add_random_cell(cells, rng),
+        1 => {
+            let cell_index = rng.gen_range(0..cells.len());
+            cells[cell_index].mutate(rng);
+        },
+        2 => remove_random_cell(cells, rng),
+        _ => (),
+    }
+}
+
+pub fn add_random_cell(cells: &mut Vec<Cell>, rng: &mut impl Rng) {
+    // println!("Adding a cell");
+    let cell_type = match rng.gen_range(0..7) { // add random rotation
+        0 => CellType::Eye(Eye {}),
+        1 => CellType::Armor,
+        2 => CellType::Killer,
+        3 => CellType::Eater,
+        4 => CellType::Producer(Producer {}),
+        5 => CellType::Mover,
+        _ => CellType::Eater,
+    };
+    let dx = rng.gen_range(-1..2);
+    let dy = rng.gen_range(-1..2);
+    let dz = rng.gen_range(-1..2);
+    let random_rotation = rng.gen_range(0..6);
+    cells.push(Cell::new(cell_type, random_rotation, dx, dy, dz));
+    // println!("An organism added a cell");
+}
+
+pub fn remove_random_cell(cells: &mut Vec<Cell>, rng: &mut impl Rng) { // removes a random cell, except the brain
+    if cells.len() > 1 {
+        let cell_index = rng.gen_range(0..cells.len());
+        if matches!(cells[cell_index].cell_type, CellType::Brain(_)) {
+            return;
         }
-        None
+        cells.remove(cell_index);
+    }
+}
+
+pub fn wander(position: &mut (i8, i8, i8), cells: &mut [Cell], rng: &mut impl Rng) { // a random step, further with more movers
+    let dx: i32 = rng.gen_range(-1..2);
+    let dy: i32 = rng.gen_range(-1..2);
+    let dz: i32 = rng.gen_range(-1..2);
+    let num_mover_cells = cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Mover)).count() as i32;
+    position.0 = shifted(position.0, dx * num_mover_cells);
+    position.1 = shifted(position.1, dy * num_mover_cells);
+    position.2 = shifted(position.2, dz * num_mover_cells);
+    // 50% chance to rotate
+    if rng.gen_range(0..2) == 0 {
+        rotate_cells(cells, rng);
+        // println!("organism rotated :)")
     }
-    pub fn move_better(&mut self, organisms: &Vec<Organism>, blocks: &Vec<Block>) {
-        let eye = self.cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Eye(_))).choose(&mut rand::thread_rng()).unwrap();
-        let (dx, dy, dz) = match eye.rotation {
-            0 => (1, 0, 0), 1 => (-1, 0, 0), 2 => (0, 1, 0), 3 => (0, -1, 0), 4 => (0, 0, 1), _ => (0, 0, -1),
+}
+
+pub fn move_better(position: &mut (i8, i8, i8), cells: &mut [Cell], around: &Surroundings, rng: &mut impl Rng) { // needs an eye
+    let eye = cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Eye(_))).choose(rng).unwrap();
+    let (dx, dy, dz) = direction_of(eye.rotation);
+    let decision = decide(*position, cells, around, eye);
+
+    if decision.score < -0.5 {
+        *position = (shifted(position.0, -dx as i32), shifted(position.1, -dy as i32), shifted(position.2, -dz as i32));
+        // println!("Running away from danger");
+    } else if decision.score > 0.5 {
+        *position = (shifted(position.0, dx as i32), shifted(position.1, dy as i32), shifted(position.2, dz as i32));
+        // println!("Moving towards food, or to kill a nearby organism");
+    } else {
+        wander(position, cells, rng);
+    }
+}
+
+pub fn decide((x, y, z): (i8, i8, i8), cells: &[Cell], around: &Surroundings, eye: &Cell) -> Decision {
+    let (dx, dy, dz) = direction_of(eye.rotation);
+    let is_in_sight = |ox: i8, oy: i8, oz: i8| (dx != 0 && dx == (ox - x).signum()) ||
+                                               (dy != 0 && dy == (oy - y).signum()) ||
+                                               (dz != 0 && dz == (oz - z).signum());
+
+    let danger_in_sight = around.organisms_within(x, y, z, 1)
+        .filter(|neighbour| is_in_sight(neighbour.x, neighbour.y, neighbour.z))
+        .map(|neighbour| neighbour.killers)
+        .sum();
+
+    let food_in_sight = around.food_in_sight(x, y, z, (dx, dy, dz));
+
+    let killers = cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Killer)).count();
+    // brain
+    let brain = brain_of(cells).unwrap();
+    let (hunger, aggression) = (brain.hunger, brain.aggression);
+    let score = (food_in_sight as f32 * 0.1 * hunger) + (killers as f32 * 0.2 * aggression) - (danger_in_sight as f32);
+    Decision { food_in_sight, danger_in_sight, killers, hunger, aggression, score }
+}
+
+pub fn rotate_cells(cells: &mut [Cell], rng: &mut impl Rng) {
+    let direction = rng.gen_range(0..6u8); // Random direction between 0 and 5
+
+    for cell in cells {
+        let (new_x, new_y, new_z) = match direction {
+            0 => (cell.local_x, -cell.local_z, cell.local_y),  // x
+            1 => (cell.local_x, cell.local_z, -cell.local_y),  // -x
+            2 => (-cell.local_z, cell.local_y, cell.local_x),  // y
+            3 => (cell.local_z, cell.local_y, -cell.local_x),  // -y
+            4 => (cell.local_x, cell.local_y, cell.local_z),  // z
+            _ => (-cell.local_x, cell.local_y, cell.local_z),  // -z
         };
-    
-        let (danger_in_sight, food_in_sight) = self.get_nearby_organisms(organisms).iter().fold((0, 0), |(danger, food), organism| {
-            let is_in_sight =   (dx != 0 && dx == (organism.x - self.x).signum()) ||
-                                (dy != 0 && dy == (organism.y - self.y).signum()) ||
-                                (dz != 0 && dz == (organism.z - self.z).signum());
-            if is_in_sight {
-                (danger + organism.cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Killer)).count(), food)
-            } else {
-                (danger, food)
-            }
-        });
-    
-        let food_in_sight = blocks.iter().filter(|block| {
-            let is_in_sight = (dx != 0 && dx == (block.x - self.x).signum()) ||
-                                (dy != 0 && dy == (block.y - self.y).signum()) ||
-                                (dz != 0 && dz == (block.z - self.z).signum());
-            is_in_sight && matches!(block.block_type, BlockType::Food)
-        }).count();
-    
-        let killers = self.cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Killer)).count();
-        // brain
-        let hunger = self.get_hunger().unwrap();
-        let aggression = self.get_aggression().unwrap();
-        let decision : f32 = (food_in_sight as f32 * 0.1 * hunger) + (killers as f32 * 0.2 * aggression) - (danger_in_sight as f32);
 
-        if decision < -0.5 {
-            self.shift(-dx, -dy, -dz);
-            // println!("Running away from danger");
-        } else if decision > 0.5 {
-            self.shift(dx, dy, dz);
-            // println!("Moving towards food, or to kill a nearby organism");
-        } else {
-            self.teleport_random();
-        }
+        cell.local_x = new_x;
+        cell.local_y = new_y;
+        cell.local_z = new_z;
+
+        // Update cell rotation
+        cell.rotation = (cell.rotation + direction as i8) % 6;
     }
-    pub fn damage_nearby_organisms(&self, organisms: &mut Vec<Organism>) {
-        for organism in organisms {
-            // println!("subtracting self.x: {}, organism.x: {}, self.y: {}, organism.y: {}, self.z: {}, organism.z: {}", self.x, organism.x, self.y, organism.y, self.z, organism.z);
-            if (self.x - organism.x).abs() <= 1 && 
-                (self.y - organism.y).abs() <= 1 && 
-                (self.z - organism.z).abs() <= 1 {
-                if organism.health > 10 { organism.health -= 10; } else { organism.health = 0; }
-                // println!("Damaging nearby organism");
-            }
-        }
+}
+
+pub fn hits_wall(position: (i8, i8, i8), cells: &[Cell], world: &World) -> bool { // the edges of the world are walls too
+    cells.iter().any(|cell| match cell_position(position, cell) {
+        Some((x, y, z)) => world.to_grid(x, y, z).is_none() || world.is_wall(x, y, z),
+        None => true,
+    })
+}
+
+pub fn direction_of(rotation: i8) -> (i8, i8, i8) { // the way a cell with this rotation faces
+    match rotation {
+        0 => (1, 0, 0), 1 => (-1, 0, 0), 2 => (0, 1, 0), 3 => (0, -1, 0), 4 => (0, 0, 1), _ => (0, 0, -1),
     }
+}
 
-    pub fn rotate(&mut self) {
-        let direction = rand::random::<u8>() % 6; // Random direction between 0 and 5
-    
-        for cell in &mut self.cells {
-            let (new_x, new_y, new_z) = match direction {
-                0 => (cell.local_x, -cell.local_z, cell.local_y),  // x
-                1 => (cell.local_x, cell.local_z, -cell.local_y),  // -x
-                2 => (-cell.local_z, cell.local_y, cell.local_x),  // y
-                3 => (cell.local_z, cell.local_y, -cell.local_x),  // -y
-                4 => (cell.local_x, cell.local_y, cell.local_z),  // z
-                _ => (-cell.local_x, cell.local_y, cell.local_z),  // -z
-            };
-    
-            cell.local_x = new_x;
-            cell.local_y = new_y;
-            cell.local_z = new_z;
-    
-            // Update cell rotation
-            cell.rotation = (cell.rotation + direction as i8) % 6;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_of_the_world_are_walls() {
+        let world = World::new(16, 16, 16); // -8 to 7 along each axis
+        let cells = vec![Cell::new(CellType::Armor, 0, 1, 0, 0)];
+        assert!(!hits_wall((6, 0, 0), &cells, &world));
+        assert!(hits_wall((7, 0, 0), &cells, &world));
+        assert!(hits_wall((i8::MAX, 0, 0), &cells, &world)); // past the end of an i8 rather than wrapped round to the other side
     }
-        
-}
\ No newline at end of file
+
+    #[test]
+    fn cells_off_the_grid_read_the_baseline() {
+        let world = World::new(16, 16, 16);
+        let mut organism = Organism::new();
+        organism.cells.push(Cell::new(CellType::Armor, 0, 1, 0, 0));
+        organism.x = i8::MAX;
+        let baseline = world.field(FieldKind::Temperature).baseline;
+        assert_eq!(organism.sample_field(&world, FieldKind::Temperature), baseline);
+    }
+}
@@ -0,0 +1,22 @@
+// finding what's under the mouse by walking a ray through the voxels
+
+use kiss3d::nalgebra::{Point3, Vector3};
+use crate::snapshot::Snapshot;
+use crate::spatial::SpatialIndex;
+
+static PICK_DISTANCE: usize = 512; // most voxels a ray passes through before giving up
+
+// the id of the first organism the ray hits, stopping at walls
+pub fn pick(sim: &Snapshot, origin: Point3<f32>, direction: Vector3<f32>) -> Option<u64> {
+    // organisms go in before walls, so a cell inside a wall still counts as hit
+    let mut index = SpatialIndex::new();
+    for organism in &sim.organisms {
+        for cell in &organism.cells {
+            index.insert(Some(organism.id), (organism.x as i32 + cell.local_x as i32, organism.y as i32 + cell.local_y as i32, organism.z as i32 + cell.local_z as i32));
+        }
+    }
+    for wall in sim.walls() {
+        index.insert(None, (wall.x as i32, wall.y as i32, wall.z as i32));
+    }
+    index.ray([origin.x, origin.y, origin.z], [direction.x, direction.y, direction.z], PICK_DISTANCE).flatten()
+}
@@ -0,0 +1,261 @@
+// the organisms of a running simulation, stored a column per field so the tick walks memory in order;
+// everyone's cells share one pool, and views give back what an Organism would look like
+
+use std::collections::HashMap;
+use crate::cell::{Cell, CellType};
+use crate::organism::{Organism, OrganismView};
+use crate::spatial::SpatialIndex;
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub struct Lineage {
+    pub parent: Option<u64>,
+    pub generation: u32,
+    pub founder: u64,
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy, Default)]
+pub struct Traits { // how many of the cell types the tick cares about an organism has, kept up to date with its cells
+    pub eyes: u16,
+    pub killers: u16,
+    pub eaters: u16,
+    pub movers: u16,
+}
+
+impl Traits {
+    pub fn of(cells: &[Cell]) -> Traits {
+        let mut traits = Traits::default();
+        for cell in cells {
+            match cell.cell_type {
+                CellType::Eye(_) => traits.eyes += 1,
+                CellType::Killer => traits.killers += 1,
+                CellType::Eater => traits.eaters += 1,
+                CellType::Mover => traits.movers += 1,
+                _ => (),
+            }
+        }
+        traits
+    }
+}
+
+// every column has one entry per organism, in the same order; organisms are only added with push
+// and only removed with retain, which is what keeps them lined up. positions only change through set_position,
+// which keeps the index of where everyone is up to date
+#[derive(Clone, Default)]
+pub struct Population {
+    pub ids: Vec<u64>,
+    pub positions: Vec<(i8, i8, i8)>,
+    pub health: Vec<u8>,
+    pub energy: Vec<u8>,
+    pub lifespan: Vec<u8>,
+    pub ages: Vec<u32>,
+    pub lineages: Vec<Lineage>,
+    pub traits: Vec<Traits>,
+    spans: Vec<(usize, usize)>, // where each organism's cells start in the pool, and how many
+    pool: Vec<Cell>,            // cells that changed size are moved to the end, the old ones are dropped by retain
+    index: SpatialIndex<u64>,   // ids, by position
+    slots: HashMap<u64, usize>, // where each id is in the columns
+}
+
+fn widen((x, y, z): (i8, i8, i8)) -> (i32, i32, i32) {
+    (x as i32, y as i32, z as i32)
+}
+
+impl Population {
+    pub fn new() -> Population {
+        Population::default()
+    }
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+    pub fn push(&mut self, organism: Organism) {
+        self.index.insert(organism.id, widen((organism.x, organism.y, organism.z)));
+        self.slots.insert(organism.id, self.ids.len());
+        self.ids.push(organism.id);
+        self.positions.push((organism.x, organism.y, organism.z));
+        self.health.push(organism.health);
+        self.energy.push(organism.energy);
+        self.lifespan.push(organism.lifespan);
+        self.ages.push(organism.age);
+        self.lineages.push(Lineage { parent: organism.parent, generation: organism.generation, founder: organism.founder });
+        self.traits.push(Traits::of(&organism.cells));
+        self.spans.push((self.pool.len(), organism.cells.len()));
+        self.pool.extend(organism.cells);
+    }
+    pub fn cells(&self, i: usize) -> &[Cell] {
+        let (start, len) = self.spans[i];
+        &self.pool[start..start + len]
+    }
+    pub fn set_cells(&mut self, i: usize, cells: Vec<Cell>) {
+        let (start, len) = self.spans[i];
+        self.traits[i] = Traits::of(&cells);
+        if cells.len() == len {
+            for (old, new) in self.pool[start..start + len].iter_mut().zip(cells) {
+                *old = new;
+            }
+        } else {
+            self.spans[i] = (self.pool.len(), cells.len());
+            self.pool.extend(cells);
+        }
+    }
+    pub fn set_position(&mut self, i: usize, position: (i8, i8, i8)) {
+        if position != self.positions[i] {
+            self.index.relocate(self.ids[i], widen(self.positions[i]), widen(position));
+            self.positions[i] = position;
+        }
+    }
+    pub fn view(&self, i: usize) -> OrganismView<'_> {
+        let (x, y, z) = self.positions[i];
+        let lineage = self.lineages[i];
+        OrganismView {
+            id: self.ids[i],
+            cells: self.cells(i),
+            health: self.health[i],
+            energy: self.energy[i],
+            lifespan: self.lifespan[i],
+            age: self.ages[i],
+            parent: lineage.parent,
+            generation: lineage.generation,
+            founder: lineage.founder,
+            x,
+            y,
+            z,
+        }
+    }
+    pub fn views(&self) -> impl Iterator<Item = OrganismView<'_>> + '_ {
+        (0..self.len()).map(|i| self.view(i))
+    }
+    pub fn find(&self, id: u64) -> Option<usize> {
+        self.slots.get(&id).copied()
+    }
+    // everyone in the cube reaching radius voxels out from position on every axis
+    pub fn within(&self, position: (i8, i8, i8), radius: i32) -> Vec<usize> {
+        self.index.within(widen(position), radius).into_iter().map(|id| self.slots[&id]).collect()
+    }
+    pub fn to_organisms(&self) -> Vec<Organism> {
+        self.views().map(|organism| organism.to_organism()).collect()
+    }
+
+    // keeps the organisms keep says yes to, in order, and packs their cells back together
+    pub fn retain(&mut self, mut keep: impl FnMut(OrganismView) -> bool) {
+        let kept: Vec<bool> = (0..self.len()).map(|i| keep(self.view(i))).collect();
+        let first_gone = kept.iter().position(|&kept| !kept).unwrap_or(kept.len());
+        for i in (first_gone..self.len()).filter(|&i| !kept[i]) {
+            self.index.remove(self.ids[i], widen(self.positions[i]));
+            self.slots.remove(&self.ids[i]);
+        }
+        let mut pool = Vec::with_capacity(self.pool.len());
+        let mut spans = Vec::with_capacity(self.spans.len());
+        for (i, &(start, len)) in self.spans.iter().enumerate() {
+            if kept[i] {
+                spans.push((pool.len(), len));
+                pool.extend_from_slice(&self.pool[start..start + len]);
+            }
+        }
+        (self.pool, self.spans) = (pool, spans);
+        retain_column(&mut self.ids, &kept);
+        retain_column(&mut self.positions, &kept);
+        retain_column(&mut self.health, &kept);
+        retain_column(&mut self.energy, &kept);
+        retain_column(&mut self.lifespan, &kept);
+        retain_column(&mut self.ages, &kept);
+        retain_column(&mut self.lineages, &kept);
+        retain_column(&mut self.traits, &kept);
+        for i in first_gone..self.len() { // everyone after the first to go has moved up
+            self.slots.insert(self.ids[i], i);
+        }
+    }
+}
+
+fn retain_column<T>(column: &mut Vec<T>, kept: &[bool]) {
+    let mut i = 0;
+    column.retain(|_| { i += 1; kept[i - 1] });
+}
+
+impl FromIterator<Organism> for Population {
+    fn from_iter<I: IntoIterator<Item = Organism>>(organisms: I) -> Population {
+        let mut population = Population::new();
+        for organism in organisms {
+            population.push(organism);
+        }
+        population
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::organism::add_random_cell;
+
+    fn check(population: &Population, reference: &[Organism], gone: &[u64]) {
+        assert_eq!(population.len(), reference.len());
+        for (i, organism) in reference.iter().enumerate() {
+            let view = population.view(i);
+            assert_eq!(ron::to_string(&view.to_organism()).unwrap(), ron::to_string(organism).unwrap(), "slot {}", i);
+            assert_eq!(format!("{:?}", population.traits[i]), format!("{:?}", Traits::of(&organism.cells)));
+            assert_eq!(population.find(organism.id), Some(i));
+            assert!(population.within((organism.x, organism.y, organism.z), 0).contains(&i));
+        }
+        for id in gone {
+            assert_eq!(population.find(*id), None);
+        }
+        let mut everyone = population.within((0, 0, 0), 128); // the index holds everyone, once, and nobody else
+        everyone.sort_unstable();
+        assert_eq!(everyone, (0..reference.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn columns_pool_and_indexes_stay_in_step() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut reference: Vec<Organism> = (0..40).map(|i| {
+            let mut organism = Organism::new();
+            organism.id = i * 3 + 1; // ids with gaps, so slots and ids differ
+            (organism.x, organism.y, organism.z) = (rng.gen_range(-20..20), rng.gen_range(-20..20), rng.gen_range(-20..20));
+            organism.energy = i as u8;
+            for _ in 0..rng.gen_range(0..4) {
+                add_random_cell(&mut organism.cells, &mut rng);
+            }
+            organism
+        }).collect();
+        let mut population: Population = reference.iter().cloned().collect();
+        let mut gone = Vec::new();
+        check(&population, &reference, &gone);
+
+        for round in 0..6 {
+            // some go from the front, the back and the middle
+            let first = reference.first().unwrap().id;
+            let last = reference.last().unwrap().id;
+            let doomed = |id: u64| id == first || id == last || (id + round).is_multiple_of(5);
+            gone.extend(reference.iter().map(|organism| organism.id).filter(|&id| doomed(id)));
+            population.retain(|organism| !doomed(organism.id));
+            reference.retain(|organism| !doomed(organism.id));
+            check(&population, &reference, &gone);
+
+            // then the rest move, grow, shrink or change cells in place
+            for (i, organism) in reference.iter_mut().enumerate() {
+                match rng.gen_range(0..4) {
+                    0 => add_random_cell(&mut organism.cells, &mut rng),
+                    1 => { organism.cells.truncate(1); }
+                    2 => organism.cells.iter_mut().skip(1).for_each(|cell| cell.cell_type = CellType::Killer),
+                    _ => {}
+                }
+                (organism.x, organism.y, organism.z) = (rng.gen_range(-20..20), rng.gen_range(-20..20), rng.gen_range(-20..20));
+                population.set_cells(i, organism.cells.clone());
+                population.set_position(i, (organism.x, organism.y, organism.z));
+            }
+            check(&population, &reference, &gone);
+
+            let mut newcomer = Organism::new(); // and someone joins
+            newcomer.id = 1000 + round;
+            population.push(newcomer.clone());
+            reference.push(newcomer);
+            check(&population, &reference, &gone);
+        }
+    }
+}
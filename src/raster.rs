@@ -0,0 +1,209 @@
+// a software voxel renderer, for pictures and videos without a window
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, Result};
+use crate::snapshot::Snapshot;
+
+static BACKGROUND: [u8; 3] = [0, 0, 0];
+static AMBIENT: f32 = 0.35; // light on faces turned away from the camera, the rest comes from the camera direction
+static NEAR: f32 = 0.1;     // perspective faces closer than this are clipped
+
+// faces of a unit cube: normal, then the four corners as offsets from the center
+static CUBE_FACES: [([i8; 3], [[f32; 3]; 4]); 6] = [
+    ([1, 0, 0], [[0.5, -0.5, -0.5], [0.5, 0.5, -0.5], [0.5, 0.5, 0.5], [0.5, -0.5, 0.5]]),
+    ([-1, 0, 0], [[-0.5, -0.5, -0.5], [-0.5, -0.5, 0.5], [-0.5, 0.5, 0.5], [-0.5, 0.5, -0.5]]),
+    ([0, 1, 0], [[-0.5, 0.5, -0.5], [-0.5, 0.5, 0.5], [0.5, 0.5, 0.5], [0.5, 0.5, -0.5]]),
+    ([0, -1, 0], [[-0.5, -0.5, -0.5], [0.5, -0.5, -0.5], [0.5, -0.5, 0.5], [-0.5, -0.5, 0.5]]),
+    ([0, 0, 1], [[-0.5, -0.5, 0.5], [0.5, -0.5, 0.5], [0.5, 0.5, 0.5], [-0.5, 0.5, 0.5]]),
+    ([0, 0, -1], [[-0.5, -0.5, -0.5], [-0.5, 0.5, -0.5], [0.5, 0.5, -0.5], [0.5, -0.5, -0.5]]),
+];
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum Projection {
+    Orthographic { extent: f32 }, // voxels visible from the top of the picture to the bottom
+    Perspective { fov: f32 },     // vertical field of view, radians
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct RasterView {
+    pub width: u32,
+    pub height: u32,
+    pub projection: Projection,
+    pub eye: [f32; 3],
+    pub at: [f32; 3],
+}
+
+impl RasterView {
+    pub fn new(width: u32, height: u32) -> RasterView { // the same view the viewer starts with
+        RasterView {
+            width,
+            height,
+            projection: Projection::Perspective { fov: std::f32::consts::FRAC_PI_4 },
+            eye: [60.0, 40.0, 60.0],
+            at: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>, // rgb, row by row from the top
+}
+
+impl Image {
+    pub fn save_png(&self, path: &str) -> Result<()> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| Error::other(e.to_string()))?;
+        writer.write_image_data(&self.pixels).map_err(|e| Error::other(e.to_string()))
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt().max(f32::EPSILON);
+    [a[0] / length, a[1] / length, a[2] / length]
+}
+
+// every occupied voxel and its color: walls and food first, so cells win where they overlap
+fn voxels(sim: &Snapshot) -> HashMap<(i16, i16, i16), (f32, f32, f32)> {
+    let mut voxels = HashMap::new();
+    for block in &sim.blocks {
+        voxels.insert((block.x as i16, block.y as i16, block.z as i16), block.block_type.color());
+    }
+    for organism in &sim.organisms {
+        for cell in &organism.cells {
+            let position = (organism.x as i16 + cell.local_x as i16, organism.y as i16 + cell.local_y as i16, organism.z as i16 + cell.local_z as i16);
+            voxels.insert(position, cell.cell_type.color());
+        }
+    }
+    voxels
+}
+
+pub fn render(sim: &Snapshot, view: &RasterView) -> Image {
+    let (width, height) = (view.width as usize, view.height as usize);
+    let mut pixels = BACKGROUND.repeat(width * height);
+    let mut depth = vec![f32::INFINITY; width * height];
+
+    let forward = normalize(sub(view.at, view.eye));
+    let side = cross(forward, [0.0, 1.0, 0.0]);
+    let right = normalize(if dot(side, side) > f32::EPSILON { side } else { [1.0, 0.0, 0.0] }); // looking straight up or down
+    let up = cross(right, forward);
+    // camera space (x right, y up, z forward) to pixels, or None if clipped
+    let project = |point: [f32; 3]| -> Option<(f32, f32, f32)> {
+        let relative = sub(point, view.eye);
+        let (x, y, z) = (dot(relative, right), dot(relative, up), dot(relative, forward));
+        let scale = match view.projection {
+            Projection::Orthographic { extent } => height as f32 / extent,
+            Projection::Perspective { fov } => {
+                if z < NEAR {
+                    return None;
+                }
+                height as f32 / 2.0 / (fov / 2.0).tan() / z
+            }
+        };
+        Some((width as f32 / 2.0 + x * scale, height as f32 / 2.0 - y * scale, z))
+    };
+
+    let voxels = voxels(sim);
+    for (&(x, y, z), &(r, g, b)) in &voxels {
+        let center = [x as f32, y as f32, z as f32];
+        for (normal, corners) in CUBE_FACES.iter() {
+            if voxels.contains_key(&(x + normal[0] as i16, y + normal[1] as i16, z + normal[2] as i16)) {
+                continue; // hidden by the neighbour
+            }
+            let normal = [normal[0] as f32, normal[1] as f32, normal[2] as f32];
+            let toward_eye = match view.projection {
+                Projection::Orthographic { .. } => [-forward[0], -forward[1], -forward[2]],
+                Projection::Perspective { .. } => normalize(sub(view.eye, center)),
+            };
+            let facing = dot(normal, toward_eye);
+            if facing <= 0.0 {
+                continue; // back face
+            }
+            let light = AMBIENT + (1.0 - AMBIENT) * facing;
+            let color = [(r * light * 255.0) as u8, (g * light * 255.0) as u8, (b * light * 255.0) as u8];
+            let projected: Option<Vec<(f32, f32, f32)>> = corners.iter()
+                .map(|corner| project([center[0] + corner[0], center[1] + corner[1], center[2] + corner[2]]))
+                .collect();
+            let Some(projected) = projected else {
+                continue;
+            };
+            for triangle in [[projected[0], projected[1], projected[2]], [projected[0], projected[2], projected[3]]] {
+                fill_triangle(&mut pixels, &mut depth, width, height, triangle, color);
+            }
+        }
+    }
+    Image { width: view.width, height: view.height, pixels }
+}
+
+// fills pixels whose centers are inside the triangle and nearer than what's already there
+fn fill_triangle(pixels: &mut [u8], depth: &mut [f32], width: usize, height: usize, triangle: [(f32, f32, f32); 3], color: [u8; 3]) {
+    let [a, b, c] = triangle;
+    let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+    let min_x = a.0.min(b.0).min(c.0).floor().max(0.0) as usize;
+    let max_x = (a.0.max(b.0).max(c.0).ceil().max(0.0) as usize).min(width);
+    let min_y = a.1.min(b.1).min(c.1).floor().max(0.0) as usize;
+    let max_y = (a.1.max(b.1).max(c.1).ceil().max(0.0) as usize).min(height);
+    for py in min_y..max_y {
+        for px in min_x..max_x {
+            let (x, y) = (px as f32 + 0.5, py as f32 + 0.5);
+            // barycentric weights, all the same sign as the area when inside
+            let wa = ((b.0 - x) * (c.1 - y) - (b.1 - y) * (c.0 - x)) / area;
+            let wb = ((c.0 - x) * (a.1 - y) - (c.1 - y) * (a.0 - x)) / area;
+            let wc = 1.0 - wa - wb;
+            if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                continue;
+            }
+            let z = wa * a.2 + wb * b.2 + wc * c.2;
+            let i = py * width + px;
+            if z < depth[i] {
+                depth[i] = z;
+                pixels[i * 3..i * 3 + 3].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+pub struct FrameRecorder { // saves a numbered picture every few ticks, e.g. to turn into a video
+    pub view: RasterView,
+    pub directory: String,
+    pub every: u64,
+    frame: u64,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: &str, every: u64, view: RasterView) -> Result<FrameRecorder> {
+        fs::create_dir_all(directory)?;
+        Ok(FrameRecorder { view, directory: directory.to_string(), every: every.max(1), frame: 0 })
+    }
+    pub fn record(&mut self, sim: &Snapshot) -> Result<()> { // call after every tick
+        if !sim.tick.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        let path = format!("{}/frame_{:05}.png", self.directory, self.frame);
+        render(sim, &self.view).save_png(&path)?;
+        self.frame += 1;
+        Ok(())
+    }
+}
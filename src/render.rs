@@ -0,0 +1,344 @@
+// keeps the kiss3d scene in step with the simulation, touching only what changed
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use kiss3d::nalgebra::{Point3, Translation3, Vector3};
+use kiss3d::resource::Mesh;
+use kiss3d::scene::SceneNode;
+use kiss3d::window::Window;
+use crate::block::BlockType;
+use crate::cell::CellType;
+use crate::organism::Organism;
+use crate::snapshot::Snapshot;
+use crate::stats::CELL_TYPE_COUNT;
+
+static WALL_CHUNK_SIZE: i16 = 16;        // walls are drawn as one mesh per chunk of this many voxels a side
+static MAX_MESH_VERTICES: usize = 65532; // kiss3d meshes index vertices with u16
+
+// each face of a cube: its normal and two edges along it, ordered so that u x v = normal
+static FACES: [([i8; 3], [f32; 3], [f32; 3]); 6] = [
+    ([1, 0, 0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([-1, 0, 0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0, 1, 0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+    ([0, -1, 0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0, 0, 1], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0, 0, -1], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+];
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum ColorMode {
+    CellType,   // each cell in its type's color
+    Species,    // organisms with the same body plan share a color
+    Lineage,    // descendants of the same placed organism share a color
+    Energy,     // the rest go from blue (low) to red (high)
+    Health,
+    Age,        // fraction of its life used up
+    Aggression,
+    Hunger,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 8] = [ColorMode::CellType, ColorMode::Species, ColorMode::Lineage, ColorMode::Energy,
+                                     ColorMode::Health, ColorMode::Age, ColorMode::Aggression, ColorMode::Hunger];
+    pub fn next(self) -> ColorMode {
+        let i = ColorMode::ALL.iter().position(|&mode| mode == self).unwrap();
+        ColorMode::ALL[(i + 1) % ColorMode::ALL.len()]
+    }
+    pub fn organism_color(&self, organism: &Organism) -> Option<(f32, f32, f32)> { // None when cells keep their own colors
+        match self {
+            ColorMode::CellType => None,
+            ColorMode::Species => {
+                let mut counts = [0; CELL_TYPE_COUNT];
+                for cell in &organism.cells {
+                    counts[cell.cell_type.index()] += 1;
+                }
+                let mut hasher = DefaultHasher::new();
+                counts.hash(&mut hasher);
+                Some(hashed_color(hasher.finish()))
+            }
+            ColorMode::Lineage => {
+                let mut hasher = DefaultHasher::new();
+                organism.founder.hash(&mut hasher);
+                Some(hashed_color(hasher.finish()))
+            }
+            ColorMode::Energy => Some(gradient(organism.energy as f32 / 100.0)),
+            ColorMode::Health => Some(gradient(organism.health as f32 / 100.0)),
+            ColorMode::Age => Some(gradient(organism.age as f32 / (organism.age + organism.lifespan as u32).max(1) as f32)),
+            ColorMode::Aggression => Some(gradient(organism.get_aggression().unwrap_or(0.0))),
+            ColorMode::Hunger => Some(gradient(organism.get_hunger().unwrap_or(0.0))),
+        }
+    }
+}
+
+fn gradient(t: f32) -> (f32, f32, f32) { // blue at 0, red at 1
+    let t = t.clamp(0.0, 1.0);
+    (t, 0.2, 1.0 - t)
+}
+
+fn hashed_color(hash: u64) -> (f32, f32, f32) { // a bright color picked by hue
+    let hue = (hash % 360) as f32 / 60.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    (0.2 + r * 0.7, 0.2 + g * 0.7, 0.2 + b * 0.7)
+}
+
+type Voxel = (i8, i8, i8);
+type Chunk = (i16, i16, i16);
+
+struct OrganismNode {
+    node: SceneNode,
+    shape: u64, // hash of the cells the node was built from
+}
+
+pub struct Renderer {
+    organisms: HashMap<u64, OrganismNode>,               // by organism id
+    food: HashMap<Voxel, (SceneNode, BlockType)>,        // one cube per voxel holding food
+    walls: HashMap<Chunk, (Vec<Voxel>, Vec<SceneNode>)>, // per chunk: the walls in it and their meshes
+    revision: Option<u64>,
+    color_mode: ColorMode,
+    xray_walls: bool, // walls drawn as wireframes so organisms inside terrain show through
+}
+
+fn shape_of(organism: &Organism) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for cell in &organism.cells {
+        std::mem::discriminant(&cell.cell_type).hash(&mut hasher);
+        (cell.local_x, cell.local_y, cell.local_z).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn chunk_of(x: i8, y: i8, z: i8) -> Chunk {
+    ((x as i16).div_euclid(WALL_CHUNK_SIZE), (y as i16).div_euclid(WALL_CHUNK_SIZE), (z as i16).div_euclid(WALL_CHUNK_SIZE))
+}
+
+impl Default for Renderer {
+    fn default() -> Renderer {
+        Renderer::new()
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Renderer {
+        Renderer {
+            organisms: HashMap::new(),
+            food: HashMap::new(),
+            walls: HashMap::new(),
+            revision: None,
+            color_mode: ColorMode::CellType,
+            xray_walls: false,
+        }
+    }
+
+    pub fn color_mode(&self) -> ColorMode {
+        self.color_mode
+    }
+
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        if mode != self.color_mode {
+            self.color_mode = mode;
+            for entry in self.organisms.values_mut() {
+                entry.shape ^= 1; // rebuild so cell type colors come back
+            }
+            self.revision = None;
+        }
+    }
+
+    pub fn xray_walls(&self) -> bool {
+        self.xray_walls
+    }
+
+    pub fn set_xray_walls(&mut self, xray: bool) {
+        self.xray_walls = xray;
+        for node in self.walls.values_mut().flat_map(|(_, nodes)| nodes.iter_mut()) {
+            set_xray(node, xray);
+        }
+    }
+
+    pub fn sync(&mut self, window: &mut Window, sim: &Snapshot) {
+        if self.revision == Some(sim.revision) {
+            return;
+        }
+        self.revision = Some(sim.revision);
+        self.sync_organisms(window, &sim.organisms);
+
+        let mut food = HashMap::new();
+        let mut walls: HashMap<Chunk, Vec<Voxel>> = HashMap::new();
+        for block in &sim.blocks {
+            match block.block_type {
+                BlockType::Wall => walls.entry(chunk_of(block.x, block.y, block.z)).or_default().push((block.x, block.y, block.z)),
+                BlockType::Food(_) => { food.insert((block.x, block.y, block.z), block.block_type.clone()); }
+            }
+        }
+        self.sync_food(window, food);
+        self.sync_walls(window, walls);
+    }
+
+    fn sync_organisms(&mut self, window: &mut Window, organisms: &[Organism]) {
+        let alive: HashSet<u64> = organisms.iter().map(|organism| organism.id).collect();
+        self.organisms.retain(|id, entry| {
+            if !alive.contains(id) {
+                window.remove_node(&mut entry.node);
+            }
+            alive.contains(id)
+        });
+        for organism in organisms {
+            let shape = shape_of(organism);
+            let entry = self.organisms.entry(organism.id).or_insert_with(|| OrganismNode { node: window.add_group(), shape: shape ^ 1 });
+            if entry.shape != shape { // mutated or rotated since last time, rebuild its cubes
+                window.remove_node(&mut entry.node);
+                entry.node = window.add_group();
+                for cell in &organism.cells {
+                    let mut cube = entry.node.add_cube(1.0, 1.0, 1.0);
+                    let (r, g, b) = cell.cell_type.color();
+                    cube.set_color(r, g, b);
+                    cube.set_local_translation(Translation3::new(cell.local_x as f32, cell.local_y as f32, cell.local_z as f32));
+                }
+                entry.shape = shape;
+            }
+            entry.node.set_local_translation(Translation3::new(organism.x as f32, organism.y as f32, organism.z as f32));
+            if let Some((r, g, b)) = self.color_mode.organism_color(organism) {
+                entry.node.set_color(r, g, b);
+            }
+        }
+    }
+
+    fn sync_food(&mut self, window: &mut Window, food: HashMap<Voxel, BlockType>) {
+        self.food.retain(|position, (node, block_type)| {
+            let keep = food.get(position) == Some(block_type);
+            if !keep {
+                window.remove_node(node);
+            }
+            keep
+        });
+        for (position, block_type) in food {
+            self.food.entry(position).or_insert_with(|| {
+                let mut cube = window.add_cube(1.0, 1.0, 1.0);
+                let (r, g, b) = block_type.color();
+                cube.set_color(r, g, b);
+                cube.set_local_translation(Translation3::new(position.0 as f32, position.1 as f32, position.2 as f32));
+                (cube, block_type)
+            });
+        }
+    }
+
+    fn sync_walls(&mut self, window: &mut Window, mut walls: HashMap<Chunk, Vec<Voxel>>) {
+        for positions in walls.values_mut() {
+            positions.sort_unstable();
+            positions.dedup();
+        }
+        self.walls.retain(|chunk, (positions, nodes)| {
+            let keep = walls.get(chunk) == Some(positions);
+            if !keep {
+                for node in nodes.iter_mut() {
+                    window.remove_node(node);
+                }
+            }
+            keep
+        });
+        for (chunk, positions) in walls {
+            if self.walls.contains_key(&chunk) {
+                continue;
+            }
+            let nodes = wall_meshes(&positions).into_iter().map(|mesh| {
+                let mut node = window.add_mesh(Rc::new(RefCell::new(mesh)), Vector3::new(1.0, 1.0, 1.0));
+                let (r, g, b) = BlockType::Wall.color();
+                node.set_color(r, g, b);
+                set_xray(&mut node, self.xray_walls);
+                node
+            }).collect();
+            self.walls.insert(chunk, (positions, nodes));
+        }
+    }
+
+    pub fn draw_eyes(&self, window: &mut Window, sim: &Snapshot) { // lines aren't scene nodes, so they're drawn every frame
+        for organism in &sim.organisms {
+            for cell in organism.cells.iter().filter(|cell| matches!(cell.cell_type, CellType::Eye(_))) {
+                let a = Point3::new((organism.x + cell.local_x) as f32, (organism.y + cell.local_y) as f32, (organism.z + cell.local_z) as f32);
+                let offset = match cell.rotation {
+                    0 => Vector3::new(1.0, 0.0, 0.0), // x
+                    1 => Vector3::new(-1.0, 0.0, 0.0), // -x
+                    2 => Vector3::new(0.0, 1.0, 0.0), // y
+                    3 => Vector3::new(0.0, -1.0, 0.0), // -y
+                    4 => Vector3::new(0.0, 0.0, 1.0), // z
+                    _ => Vector3::new(0.0, 0.0, -1.0), // -z
+                };
+                window.draw_line(&a, &(a + offset), &Point3::new(1.0, 1.0, 1.0));
+            }
+        }
+    }
+
+    pub fn draw_selection(&self, window: &mut Window, sim: &Snapshot, id: u64) { // outlines the organism's bounding box
+        let Some(organism) = sim.organisms.iter().find(|organism| organism.id == id) else {
+            return;
+        };
+        let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        for cell in &organism.cells {
+            let position = Point3::new((organism.x + cell.local_x) as f32, (organism.y + cell.local_y) as f32, (organism.z + cell.local_z) as f32);
+            min = min.inf(&position);
+            max = max.sup(&position);
+        }
+        let (min, max) = (min - Vector3::repeat(0.6), max + Vector3::repeat(0.6)); // just outside the cubes
+        let corner = |i: usize| Point3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    window.draw_line(&corner(i), &corner(i | bit), &Point3::new(1.0, 1.0, 0.0));
+                }
+            }
+        }
+    }
+}
+
+fn set_xray(node: &mut SceneNode, xray: bool) {
+    node.set_surface_rendering_activation(!xray);
+    node.set_lines_width(if xray { 1.0 } else { 0.0 });
+}
+
+// builds meshes for a set of walls, leaving out faces hidden by a neighbouring wall
+fn wall_meshes(positions: &[Voxel]) -> Vec<Mesh> {
+    let solid: HashSet<Voxel> = positions.iter().cloned().collect();
+    let mut meshes = Vec::new();
+    let mut coords = Vec::new();
+    let mut faces = Vec::new();
+    for &(x, y, z) in positions {
+        for (normal, u, v) in FACES.iter() {
+            let neighbour = (x.wrapping_add(normal[0]), y.wrapping_add(normal[1]), z.wrapping_add(normal[2]));
+            if solid.contains(&neighbour) {
+                continue;
+            }
+            if coords.len() + 4 > MAX_MESH_VERTICES {
+                meshes.push(Mesh::new(std::mem::take(&mut coords), std::mem::take(&mut faces), None, None, false));
+            }
+            let center = Vector3::new(x as f32 + normal[0] as f32 * 0.5, y as f32 + normal[1] as f32 * 0.5, z as f32 + normal[2] as f32 * 0.5);
+            let (u, v) = (Vector3::new(u[0], u[1], u[2]) * 0.5, Vector3::new(v[0], v[1], v[2]) * 0.5);
+            let first = coords.len() as u16;
+            for corner in [-u - v, u - v, u + v, -u + v] {
+                coords.push(Point3::from(center + corner));
+            }
+            faces.push(Point3::new(first, first + 1, first + 2));
+            faces.push(Point3::new(first, first + 2, first + 3));
+        }
+    }
+    if !coords.is_empty() {
+        meshes.push(Mesh::new(coords, faces, None, None, false));
+    }
+    meshes
+}
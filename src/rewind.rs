@@ -0,0 +1,264 @@
+// the last few hundred ticks, so something that just happened can be looked at again, or a new run forked from it
+// every KEYFRAME_INTERVAL ticks a copy of the simulation is kept, its fields packed down to what's away from the baseline; for the ticks in between, how the snapshot changed
+// and the commands that went in before the step. a past tick is its keyframe's snapshot with the changes played forward,
+// a fork is its keyframe's simulation stepped forward again with the same commands. replays keep no simulation, so can't fork
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use crate::block::Block;
+use crate::cell::Cell;
+use crate::field::PackedField;
+use crate::organism::Organism;
+use crate::simulation::{Command, Simulation};
+use crate::snapshot::Snapshot;
+
+pub static REWIND_TICKS: u64 = 500;  // how far back it goes, at least
+static KEYFRAME_INTERVAL: u64 = 100; // ticks between copies of the simulation
+
+#[derive(Clone, Copy)]
+struct Vitals { // the parts of an organism that change nearly every tick
+    id: u64,
+    x: i8,
+    y: i8,
+    z: i8,
+    health: u8,
+    energy: u8,
+    lifespan: u8,
+    age: u32,
+}
+
+impl Vitals {
+    fn of(organism: &Organism) -> Vitals {
+        Vitals {
+            id: organism.id,
+            x: organism.x,
+            y: organism.y,
+            z: organism.z,
+            health: organism.health,
+            energy: organism.energy,
+            lifespan: organism.lifespan,
+            age: organism.age,
+        }
+    }
+    fn apply(&self, organism: &mut Organism) {
+        (organism.x, organism.y, organism.z) = (self.x, self.y, self.z);
+        (organism.health, organism.energy, organism.lifespan, organism.age) = (self.health, self.energy, self.lifespan, self.age);
+    }
+}
+
+enum Change { // one organism of the later snapshot
+    Carried(Vitals),             // same cells as before
+    Reshaped(Vitals, Vec<Cell>), // moved and turned, or mutated
+    Joined(Box<Organism>),       // wasn't there before
+}
+
+struct Delta { // how one tick's snapshot differs from the one before it
+    tick: u64,
+    revision: u64,
+    organisms: Vec<Change>, // in the later snapshot's order; anyone not mentioned is gone
+    kept_blocks: usize,     // blocks the same as the start of the earlier list
+    blocks: Vec<Block>,     // the rest of the later list
+}
+
+impl Delta {
+    fn between(before: &Snapshot, after: &Snapshot) -> Delta {
+        let previous: HashMap<u64, &Organism> = before.organisms.iter().map(|organism| (organism.id, organism)).collect();
+        let organisms = after.organisms.iter().map(|organism| match previous.get(&organism.id) {
+            Some(old) if old.cells == organism.cells => Change::Carried(Vitals::of(organism)),
+            Some(_) => Change::Reshaped(Vitals::of(organism), organism.cells.clone()),
+            None => Change::Joined(Box::new(organism.clone())),
+        }).collect();
+        // walls and food that was there before come first and keep their order, so this is usually most of them
+        let kept_blocks = before.blocks.iter().zip(&after.blocks).take_while(|(old, new)| old == new).count();
+        Delta {
+            tick: after.tick,
+            revision: after.revision,
+            organisms,
+            kept_blocks,
+            blocks: after.blocks[kept_blocks..].to_vec(),
+        }
+    }
+    fn apply(&self, before: &Snapshot) -> Snapshot {
+        let previous: HashMap<u64, &Organism> = before.organisms.iter().map(|organism| (organism.id, organism)).collect();
+        let carry = |vitals: &Vitals, cells: Option<&Vec<Cell>>| {
+            let mut organism = Organism::clone(previous[&vitals.id]);
+            vitals.apply(&mut organism);
+            if let Some(cells) = cells {
+                organism.cells = cells.clone();
+            }
+            organism
+        };
+        let organisms = self.organisms.iter().map(|change| match change {
+            Change::Carried(vitals) => carry(vitals, None),
+            Change::Reshaped(vitals, cells) => carry(vitals, Some(cells)),
+            Change::Joined(organism) => Organism::clone(organism),
+        }).collect();
+        let mut blocks = before.blocks[..self.kept_blocks].to_vec();
+        blocks.extend(self.blocks.iter().cloned());
+        Snapshot { tick: self.tick, revision: self.revision, organisms, blocks, width: before.width, height: before.height, depth: before.depth }
+    }
+}
+
+struct Keyframe {
+    simulation: Option<Simulation>, // without its fields, which are kept packed; a full one is three of them the size of the world
+    fields: Vec<PackedField>,
+    snapshot: Arc<Snapshot>,
+    ticks: Vec<(Vec<Command>, Delta)>, // the ticks after this one, each with the commands applied before its step
+}
+
+impl Keyframe {
+    fn new(simulation: Option<&Simulation>, snapshot: Arc<Snapshot>) -> Keyframe {
+        let mut simulation = simulation.cloned();
+        let fields = match &mut simulation {
+            Some(simulation) => std::mem::take(&mut simulation.world.fields).iter().map(|field| field.pack()).collect(),
+            None => Vec::new(),
+        };
+        Keyframe { simulation, fields, snapshot, ticks: Vec::new() }
+    }
+    fn tick(&self) -> u64 {
+        self.snapshot.tick
+    }
+    fn last_tick(&self) -> u64 {
+        self.tick() + self.ticks.len() as u64
+    }
+}
+
+pub struct Rewind {
+    keyframes: VecDeque<Keyframe>,
+    latest: Arc<Snapshot>, // the last tick recorded, which the next one is compared against
+    pending: Vec<Command>, // applied since then, they belong to the next tick
+}
+
+impl Rewind {
+    pub fn new(simulation: Option<&Simulation>, snapshot: Arc<Snapshot>) -> Rewind {
+        let keyframe = Keyframe::new(simulation, Arc::clone(&snapshot));
+        Rewind { keyframes: VecDeque::from([keyframe]), latest: snapshot, pending: Vec::new() }
+    }
+
+    pub fn command(&mut self, command: &Command) { // call before applying it
+        self.pending.push(command.clone());
+    }
+
+    pub fn record(&mut self, simulation: Option<&Simulation>, snapshot: Arc<Snapshot>) { // call after every step
+        let commands = std::mem::take(&mut self.pending);
+        let last = self.keyframes.back_mut().unwrap();
+        if snapshot.tick >= last.tick() + KEYFRAME_INTERVAL {
+            self.keyframes.push_back(Keyframe::new(simulation, Arc::clone(&snapshot)));
+        } else {
+            last.ticks.push((commands, Delta::between(&self.latest, &snapshot)));
+        }
+        // the oldest keyframe goes once the one after it reaches back far enough on its own
+        while self.keyframes.len() > 1 && snapshot.tick - self.keyframes[1].tick() >= REWIND_TICKS {
+            self.keyframes.pop_front();
+        }
+        self.latest = snapshot;
+    }
+
+    pub fn range(&self) -> (u64, u64) { // the oldest and newest ticks there are
+        (self.keyframes.front().unwrap().tick(), self.keyframes.back().unwrap().last_tick())
+    }
+
+    fn keyframe_for(&self, tick: u64) -> Option<&Keyframe> {
+        self.keyframes.iter().rev().find(|keyframe| keyframe.tick() <= tick).filter(|keyframe| tick <= keyframe.last_tick())
+    }
+
+    pub fn snapshot_at(&self, tick: u64) -> Option<Arc<Snapshot>> {
+        let keyframe = self.keyframe_for(tick)?;
+        let mut snapshot = Arc::clone(&keyframe.snapshot);
+        for (_, delta) in &keyframe.ticks[..(tick - keyframe.tick()) as usize] {
+            snapshot = Arc::new(delta.apply(&snapshot));
+        }
+        Some(snapshot)
+    }
+
+    pub fn fork_at(&self, tick: u64) -> Option<Simulation> { // the simulation as it was at the end of that tick
+        let keyframe = self.keyframe_for(tick)?;
+        let mut simulation = keyframe.simulation.clone()?;
+        simulation.world.fields = keyframe.fields.iter().map(PackedField::unpack).collect();
+        for (commands, _) in &keyframe.ticks[..(tick - keyframe.tick()) as usize] {
+            for command in commands {
+                simulation.apply(command.clone());
+            }
+            simulation.step();
+        }
+        Some(simulation)
+    }
+
+    pub fn truncate(&mut self, tick: u64) { // forgets everything after tick, for when the run carries on from there instead
+        let Some(latest) = self.snapshot_at(tick) else {
+            return;
+        };
+        while self.keyframes.len() > 1 && self.keyframes.back().unwrap().tick() > tick {
+            self.keyframes.pop_back();
+        }
+        let last = self.keyframes.back_mut().unwrap();
+        last.ticks.truncate((tick - last.tick()) as usize);
+        self.latest = latest;
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definition::OrganismDefinition;
+    use crate::world::World;
+
+    fn commands() -> Vec<(u64, Command)> {
+        let mut commands: Vec<(u64, Command)> = ["blob", "grazer", "hunter", "plant"].into_iter().enumerate()
+            .map(|(i, name)| (0, Command::Spawn { definition: OrganismDefinition::preset(name), x: i as i8 * 6, y: 0, z: 0 }))
+            .collect();
+        commands.push((60, Command::Cull { fraction: 0.3 }));
+        commands.push((90, Command::PlaceFood { x: 2, y: 2, z: 2 }));
+        commands.push((130, Command::Meteor { x: 0, y: 0, z: 0, radius: 4 }));
+        commands.push((140, Command::RemoveRegion { min: (10, -5, -5), max: (20, 5, 5) }));
+        commands
+    }
+
+    fn advance(simulation: &mut Simulation, mut rewind: Option<&mut Rewind>, commands: &[(u64, Command)]) { // the way the runner steps
+        let tick = simulation.tick;
+        for (_, command) in commands.iter().filter(|(at, _)| *at == tick) {
+            if let Some(rewind) = rewind.as_deref_mut() {
+                rewind.command(command);
+            }
+            simulation.apply(command.clone());
+        }
+        simulation.step();
+        if let Some(rewind) = rewind {
+            rewind.record(Some(simulation), Arc::new(Snapshot::of(simulation)));
+        }
+    }
+
+    #[test]
+    fn past_ticks_and_forks_match_the_run() {
+        let commands = commands();
+        let mut simulation = Simulation::new(World::new(64, 64, 64), Vec::new(), Vec::new(), 3);
+        let mut rewind = Rewind::new(Some(&simulation), Arc::new(Snapshot::of(&simulation)));
+        let mut seen = vec![Snapshot::of(&simulation).to_ron().unwrap()];
+        let mut fields = Vec::new(); // as they were at tick 135, just after the meteor
+        for _ in 0..250 {
+            advance(&mut simulation, Some(&mut rewind), &commands);
+            seen.push(Snapshot::of(&simulation).to_ron().unwrap());
+            if simulation.tick == 135 {
+                fields = simulation.world.fields.iter().map(|field| field.values().to_vec()).collect();
+            }
+        }
+        assert_eq!(rewind.range(), (0, 250));
+        for (tick, expected) in seen.iter().enumerate() {
+            assert_eq!(&rewind.snapshot_at(tick as u64).unwrap().to_ron().unwrap(), expected, "tick {}", tick);
+        }
+        assert!(rewind.snapshot_at(251).is_none());
+
+        for tick in [0, 100, 199] {
+            assert_eq!(&Snapshot::of(&rewind.fork_at(tick).unwrap()).to_ron().unwrap(), &seen[tick as usize], "fork at {}", tick);
+        }
+        let mut fork = rewind.fork_at(125).unwrap(); // stepped on through the meteor
+        while fork.tick < 160 {
+            if fork.tick == 135 {
+                assert!(fork.world.fields.iter().zip(&fields).all(|(field, values)| field.values() == &values[..]), "fields at 135");
+            }
+            advance(&mut fork, None, &commands);
+            assert_eq!(&Snapshot::of(&fork).to_ron().unwrap(), &seen[fork.tick as usize], "fork at 125, tick {}", fork.tick);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+// runs the simulation on its own thread, with pause, single step and speed controls, and rewinding through recent ticks
+// the thread owns the simulation; everyone else reads the snapshots it publishes and sends it commands
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use arc_swap::ArcSwap;
+use crate::simulation::{Command, Simulation};
+use crate::snapshot::Snapshot;
+use crate::rewind::Rewind;
+use crate::events::{Event, Replay};
+
+// time between ticks at each speed, slowest first; 0 runs as fast as possible
+static TICK_INTERVALS_MS: [u64; 8] = [1000, 500, 200, 100, 50, 20, 10, 0];
+static DEFAULT_SPEED: usize = 2;
+
+enum Source { // what the thread steps
+    Live(Box<Simulation>),
+    Replay(Box<Replay>), // a recorded run played back; commands and forks don't apply to it
+}
+
+impl Source {
+    fn step(&mut self) -> Vec<Event> { // what happened, if the simulation is recording
+        match self {
+            Source::Live(simulation) => {
+                simulation.step();
+                simulation.take_events()
+            }
+            Source::Replay(replay) => {
+                replay.step().ok(); // load turns away logs that don't fit, and one that still doesn't just finishes there
+                Vec::new()
+            }
+        }
+    }
+    fn snapshot(&self) -> Snapshot {
+        match self {
+            Source::Live(simulation) => Snapshot::of(simulation),
+            Source::Replay(replay) => replay.snapshot().clone(),
+        }
+    }
+    fn simulation(&self) -> Option<&Simulation> {
+        match self {
+            Source::Live(simulation) => Some(simulation.as_ref()),
+            Source::Replay(_) => None,
+        }
+    }
+    fn is_finished(&self) -> bool {
+        matches!(self, Source::Replay(replay) if replay.is_finished())
+    }
+}
+
+struct Control {
+    paused: bool,
+    pending_steps: u64,     // single steps requested while paused
+    speed: usize,           // index into TICK_INTERVALS_MS
+    commands: Vec<Command>, // applied as soon as the thread wakes up, paused or not
+    fork: Option<u64>,      // a past tick to carry on from instead, dropping everything after it
+}
+
+pub struct Runner {
+    latest: Arc<ArcSwap<Snapshot>>,
+    control: Arc<(Mutex<Control>, Condvar)>, // the condvar wakes the thread whenever the controls change
+    rewind: Arc<Mutex<Rewind>>,
+    rewound: Mutex<Option<Arc<Snapshot>>>,   // the past tick being looked at instead of the newest one
+}
+
+impl Runner {
+    pub fn spawn(simulation: Simulation) -> Runner {
+        Runner::spawn_with_observer(simulation, |_, _| {})
+    }
+
+    // observer runs on the thread after every tick, with what happened in it if the simulation is recording events
+    pub fn spawn_with_observer(simulation: Simulation, observer: impl FnMut(&Snapshot, &[Event]) + Send + 'static) -> Runner {
+        Runner::spawn_source(Source::Live(Box::new(simulation)), observer)
+    }
+
+    pub fn spawn_replay(replay: Replay) -> Runner { // plays a recorded run back instead, pausing at the end
+        Runner::spawn_source(Source::Replay(Box::new(replay)), |_, _| {})
+    }
+
+    fn spawn_source(source: Source, observer: impl FnMut(&Snapshot, &[Event]) + Send + 'static) -> Runner {
+        let snapshot = Arc::new(source.snapshot());
+        let rewind = Arc::new(Mutex::new(Rewind::new(source.simulation(), Arc::clone(&snapshot))));
+        let latest = Arc::new(ArcSwap::new(snapshot));
+        let control = Arc::new((Mutex::new(Control { paused: false, pending_steps: 0, speed: DEFAULT_SPEED, commands: Vec::new(), fork: None }), Condvar::new()));
+        let (thread_latest, thread_control, thread_rewind) = (Arc::clone(&latest), Arc::clone(&control), Arc::clone(&rewind));
+        thread::spawn(move || run(source, &thread_latest, &thread_control, &thread_rewind, observer));
+        Runner { latest, control, rewind, rewound: Mutex::new(None) }
+    }
+
+    pub fn snapshot(&self) -> Arc<Snapshot> { // never waits for the tick in progress; a past one while rewound
+        match &*self.rewound.lock().unwrap() {
+            Some(snapshot) => Arc::clone(snapshot),
+            None => self.latest.load_full(),
+        }
+    }
+
+    fn change(&self, f: impl FnOnce(&mut Control)) {
+        let (control, wake) = &*self.control;
+        f(&mut control.lock().unwrap());
+        wake.notify_all();
+    }
+
+    pub fn apply(&self, command: Command) { // shows up in the next snapshot
+        self.change(|control| control.commands.push(command));
+    }
+    pub fn pause(&self) {
+        self.change(|control| control.paused = true);
+    }
+    pub fn resume(&self) { // back to the newest tick if rewound
+        self.live();
+        self.change(|control| { control.paused = false; control.pending_steps = 0; });
+    }
+    pub fn toggle_pause(&self) {
+        if self.is_paused() { self.resume() } else { self.pause() }
+    }
+    pub fn step(&self) { // pauses if running, then advances one tick; while rewound, moves one tick forward through the past
+        if self.rewound_tick().is_some() {
+            self.rewind_by(1);
+            return;
+        }
+        self.change(|control| {
+            control.paused = true;
+            control.pending_steps += 1;
+        });
+    }
+    pub fn faster(&self) {
+        self.change(|control| control.speed = (control.speed + 1).min(TICK_INTERVALS_MS.len() - 1));
+    }
+    pub fn slower(&self) {
+        self.change(|control| control.speed = control.speed.saturating_sub(1));
+    }
+    pub fn set_tick_interval(&self, interval: Option<Duration>) { // None for as fast as possible; snaps to the nearest speed
+        let ms = interval.map_or(0, |interval| interval.as_millis() as u64);
+        let speed = (0..TICK_INTERVALS_MS.len()).min_by_key(|&i| TICK_INTERVALS_MS[i].abs_diff(ms)).unwrap();
+        self.change(|control| control.speed = speed);
+    }
+    pub fn tick_interval(&self) -> Option<Duration> {
+        let ms = TICK_INTERVALS_MS[self.control.0.lock().unwrap().speed];
+        if ms == 0 { None } else { Some(Duration::from_millis(ms)) }
+    }
+    pub fn is_paused(&self) -> bool {
+        self.control.0.lock().unwrap().paused
+    }
+
+    // moves through the recorded ticks, backwards for negative ticks, pausing first; going past the newest is live again
+    pub fn rewind_by(&self, ticks: i64) {
+        self.pause();
+        let rewind = self.rewind.lock().unwrap();
+        let (oldest, newest) = rewind.range();
+        let current = self.rewound_tick().unwrap_or(newest);
+        let target = current.saturating_add_signed(ticks).clamp(oldest, newest);
+        *self.rewound.lock().unwrap() = if target == newest { None } else { rewind.snapshot_at(target) };
+    }
+    pub fn live(&self) {
+        *self.rewound.lock().unwrap() = None;
+    }
+    pub fn rewound_tick(&self) -> Option<u64> {
+        self.rewound.lock().unwrap().as_ref().map(|snapshot| snapshot.tick)
+    }
+    pub fn rewind_range(&self) -> (u64, u64) {
+        self.rewind.lock().unwrap().range()
+    }
+    pub fn fork(&self) { // the run carries on from the tick being looked at, and what came after it is dropped
+        if let Some(tick) = self.rewound_tick() {
+            self.change(|control| control.fork = Some(tick));
+            self.live();
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if let Some(tick) = self.rewound_tick() {
+            let (oldest, newest) = self.rewind_range();
+            return format!("rewound to tick {} ({} to {} kept)", tick, oldest, newest);
+        }
+        let state = if self.is_paused() { "paused" } else { "running" };
+        match self.tick_interval() {
+            Some(interval) => format!("{}, {} ms/tick", state, interval.as_millis()),
+            None => format!("{}, as fast as possible", state),
+        }
+    }
+}
+
+fn run(mut source: Source, latest: &ArcSwap<Snapshot>, control: &(Mutex<Control>, Condvar), rewind: &Mutex<Rewind>, mut observer: impl FnMut(&Snapshot, &[Event])) {
+    let (control, wake) = control;
+    let mut last_tick = Instant::now();
+    loop {
+        // wait until there's a fork or command to apply or a tick is due
+        let (fork, commands, step) = {
+            let mut guard = control.lock().unwrap();
+            loop {
+                if guard.fork.is_some() || !guard.commands.is_empty() {
+                    break (guard.fork.take(), std::mem::take(&mut guard.commands), false);
+                }
+                if guard.paused {
+                    if guard.pending_steps > 0 {
+                        guard.pending_steps -= 1;
+                        break (None, Vec::new(), true);
+                    }
+                    guard = wake.wait(guard).unwrap();
+                    continue;
+                }
+                // waiting on the condvar rather than sleeping means speed changes apply straight away
+                let interval = Duration::from_millis(TICK_INTERVALS_MS[guard.speed]);
+                let elapsed = last_tick.elapsed();
+                if elapsed >= interval {
+                    break (None, Vec::new(), true);
+                }
+                guard = wake.wait_timeout(guard, interval - elapsed).unwrap().0;
+            }
+        };
+        if let (Some(tick), Source::Live(simulation)) = (fork, &mut source) {
+            let mut rewind = rewind.lock().unwrap();
+            if let Some(mut forked) = rewind.fork_at(tick) {
+                forked.take_events(); // stepping back up to the fork happened already as far as anyone recording knows
+                rewind.truncate(tick);
+                **simulation = forked;
+            }
+        }
+        if let Source::Live(simulation) = &mut source {
+            for command in commands {
+                rewind.lock().unwrap().command(&command);
+                simulation.apply(command);
+            }
+        }
+        let step = step && !source.is_finished();
+        if source.is_finished() {
+            control.lock().unwrap().paused = true;
+        }
+        let events = if step {
+            last_tick = Instant::now();
+            source.step()
+        } else {
+            Vec::new()
+        };
+        let snapshot = Arc::new(source.snapshot());
+        if step {
+            observer(&snapshot, &events);
+            rewind.lock().unwrap().record(source.simulation(), Arc::clone(&snapshot));
+        }
+        latest.store(snapshot);
+    }
+}
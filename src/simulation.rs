@@ -0,0 +1,649 @@
+// the simulation core: entities, the world they live in, and commands for changing them at runtime
+
+use std::collections::HashMap;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use crate::organism::{Organism, REPRODUCTION_COST, cell_position, hits_wall, move_better, mutate_cells, wander};
+use crate::population::Population;
+use crate::cell::{Cell, CellType};
+use crate::world::World;
+use crate::block::{Block, BlockType, FoodSource};
+use crate::field::FieldKind;
+use crate::definition::OrganismDefinition;
+use crate::spatial::Surroundings;
+use crate::events::{record, Cause, Event};
+
+// 1 in x chances
+static CHANCE_OF_REPRODUCTION: i8 = 20;             // how likely an organism is to reproduce
+static CHANCE_OF_MUTATION: i8 = 100;                // random mutation apart from reproduction
+static CHANCE_OF_FOOD_PRODUCTION: i8 = 30;          // chance of a producer cell producing food
+
+static MAX_ORGANISMS: usize = 100;                  // soft limit; multiple organisms can be created in a single tick
+static MAX_BLOCKS: usize = 100;                     // limit on food blocks, walls don't count; this can by bypassed when an organism dies
+static FOOD_DECAYS_INTO_SOIL: bool = true;          // rotting food feeds the soil instead of vanishing
+static SOIL_UPTAKE: u8 = 5;                         // max soil nutrients a producer puts into each food block
+pub static ATTACK_DAMAGE: u8 = 10;                  // health lost to each attack
+
+// metabolism
+pub static ENERGY_PER_TICK: u8 = 2;                 // what staying alive costs, one more when uncomfortable
+static HEAT_PER_CELL: f32 = 0.002;                  // living cells warm the voxel they're in
+static COMFORT_RANGE: f32 = 0.25;                   // organisms further than this from 0.5 temperature burn extra energy
+static TOXIN_PER_CARCASS_DECAY: f32 = 0.5;          // rotting carcasses release toxins as well as nutrients
+static TOXIN_DAMAGE_THRESHOLD: f32 = 1.0;           // organisms in more toxins than this lose health
+
+// every organism draws from its own generator, seeded by the tick and its id,
+// so the numbers it gets don't depend on which thread runs it or who went before it
+fn organism_rng(tick_seed: u64, id: u64) -> StdRng {
+    StdRng::seed_from_u64(tick_seed ^ id.wrapping_mul(0xbf58_476d_1ce4_e5b9))
+}
+
+struct Movement { // where a mover means to end up, and which way it'll be turned
+    x: i8,
+    y: i8,
+    z: i8,
+    cells: Vec<Cell>,
+}
+
+#[derive(Default)]
+struct Intent { // what an organism means to do this tick, decided against the state at the start of it
+    child: Option<Organism>,
+    food: Option<Block>,
+    mutate: bool,
+    eat: Vec<(i8, i8, i8)>, // voxels with food next to it
+    attack: Vec<usize>,     // organisms next to it, when it has killer cells
+    movement: Option<Movement>,
+}
+
+// sense and decide: reads everything, changes nothing
+fn intend(population: &Population, i: usize, around: &Surroundings, world: &World, room_for_children: bool, rng: &mut StdRng) -> Intent {
+    let mut intent = Intent::default();
+    let organism = population.view(i);
+    let traits = population.traits[i];
+    if rng.gen_range(0..CHANCE_OF_REPRODUCTION) == 0 && room_for_children {
+        let mut child = organism.offspring(rng);
+        if rng.gen_range(0..2) == 0 {
+            child.mutate(rng); // reproduced organisms have a 50% chance of mutation
+        }
+        if !child.hits_wall(world) {
+            intent.child = Some(child);
+        }
+    }
+    if rng.gen_range(0..CHANCE_OF_FOOD_PRODUCTION) == 0 {
+        intent.food = organism.produce_food(rng); // nothing without a producer cell
+    }
+    intent.mutate = rng.gen_range(0..CHANCE_OF_MUTATION) == 0; // random mutation apart from reproduction
+    if traits.eaters > 0 {
+        intent.eat = around.food_within(organism.x, organism.y, organism.z, 1);
+    }
+    if traits.killers > 0 {
+        intent.attack = population.within((organism.x, organism.y, organism.z), 1).into_iter()
+            .filter(|&other| other != i)
+            .collect();
+    }
+    // movers with eyes decide where to go, those without wander; walls block movement
+    if traits.movers > 0 {
+        let mut moved = (organism.x, organism.y, organism.z);
+        let mut cells = organism.cells.to_vec();
+        if traits.eyes > 0 {
+            move_better(&mut moved, &mut cells, around, rng);
+        } else {
+            wander(&mut moved, &mut cells, rng);
+        }
+        if !hits_wall(moved, &cells, world) {
+            intent.movement = Some(Movement { x: moved.0, y: moved.1, z: moved.2, cells });
+        }
+    }
+    intent
+}
+
+static NEWBORN: usize = usize::MAX; // who claims the voxels of children, which aren't in the population yet
+
+#[derive(Default)]
+struct Claims { // which organism, by slot, each voxel with someone's cell in it belongs to this tick
+    owners: HashMap<(i8, i8, i8), usize>,
+}
+
+impl Claims {
+    fn voxels(position: (i8, i8, i8), cells: &[Cell]) -> impl Iterator<Item = (i8, i8, i8)> + '_ {
+        cells.iter().filter_map(move |cell| cell_position(position, cell))
+    }
+    fn is_free_for(&self, i: usize, position: (i8, i8, i8), cells: &[Cell]) -> bool {
+        Claims::voxels(position, cells).all(|voxel| self.owners.get(&voxel).is_none_or(|&owner| owner == i && i != NEWBORN))
+    }
+    fn take(&mut self, i: usize, position: (i8, i8, i8), cells: &[Cell]) { // anyone already there keeps it
+        for voxel in Claims::voxels(position, cells) {
+            self.owners.entry(voxel).or_insert(i);
+        }
+    }
+    fn give_up(&mut self, i: usize, position: (i8, i8, i8), cells: &[Cell]) {
+        for voxel in Claims::voxels(position, cells) {
+            if self.owners.get(&voxel) == Some(&i) {
+                self.owners.remove(&voxel);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Outcome { // what a tick leaves for the simulation to finish off
+    pub newborns: Vec<Organism>,    // ids aren't final, the simulation hands them out as they join
+    pub events: Option<Vec<Event>>, // what happened, when it's being recorded
+}
+
+// every organism decides what to do against the same state, in parallel; then the intents are resolved:
+// - every attack lands, ATTACK_DAMAGE health each, so it doesn't matter who struck first
+// - a food block next to several eaters goes to the lowest id
+// - bodies don't overlap: movers go in id order, each only where nobody's cells are, or it stays put;
+//   children and mutations that would land on someone don't happen
+// - children, food and carcasses are added in id order while there's room
+// each organism's random numbers come from its own generator, so a seed always gives the same run
+pub fn update_world(population: &mut Population, outcome: &mut Outcome, blocks: &mut Vec<Block>, max_organisms: usize, max_blocks: usize, sim_world: &mut World, tick_seed: u64) {
+    let (new_organisms, events) = (&mut outcome.newborns, &mut outcome.events);
+    let mut food_count = blocks.iter().filter(|block| matches!(block.block_type, BlockType::Food(_))).count();
+    let mut rngs: Vec<StdRng> = population.ids.iter().map(|&id| organism_rng(tick_seed, id)).collect();
+    let mut order: Vec<usize> = (0..population.len()).collect();
+    order.sort_unstable_by_key(|&i| population.ids[i]);
+
+    // sense and decide
+    let world = &*sim_world;
+    let around = Surroundings { population, food: &world.food };
+    let room_for_children = population.len() < max_organisms;
+    let mut intents: Vec<Intent> = rngs.par_iter_mut().enumerate()
+        .map(|(i, rng)| intend(population, i, &around, world, room_for_children, rng))
+        .collect();
+
+    // resolve what can conflict
+    let mut hits = vec![0usize; population.len()];
+    for (attacker, intent) in intents.iter().enumerate() {
+        for &target in &intent.attack {
+            hits[target] += 1;
+            record(events, || Event::Attacked { attacker: population.ids[attacker], target: population.ids[target] });
+        }
+    }
+    let mut eaten_by = HashMap::new(); // voxels with food, and who eats it
+    let mut claims = Claims::default();
+    for &i in &order {
+        claims.take(i, population.positions[i], population.cells(i));
+    }
+    for &i in &order {
+        for &voxel in &intents[i].eat {
+            eaten_by.entry(voxel).or_insert(i);
+        }
+        if let Some(movement) = &intents[i].movement {
+            if claims.is_free_for(i, (movement.x, movement.y, movement.z), &movement.cells) {
+                claims.give_up(i, population.positions[i], population.cells(i));
+                claims.take(i, (movement.x, movement.y, movement.z), &movement.cells);
+            } else {
+                intents[i].movement = None;
+            }
+        }
+    }
+    let eater_of = |block: &Block| match block.block_type {
+        BlockType::Food(_) => eaten_by.get(&(block.x, block.y, block.z)).copied(),
+        BlockType::Wall => None,
+    };
+    for (block, food) in blocks.iter().enumerate() {
+        if let Some(eater) = eater_of(food) {
+            population.energy[eater] = food.feed(population.energy[eater]);
+            record(events, || Event::Ate { id: population.ids[eater], block });
+        }
+    }
+    blocks.retain(|block| {
+        let eaten = eater_of(block).is_some();
+        if eaten {
+            sim_world.remove_block(block);
+        }
+        !eaten
+    });
+
+    for &i in &order {
+        if let Some(child) = intents[i].child.take() {
+            let position = (child.x, child.y, child.z);
+            if claims.is_free_for(NEWBORN, position, &child.cells) {
+                claims.take(NEWBORN, position, &child.cells);
+                population.energy[i] = population.energy[i].saturating_sub(REPRODUCTION_COST);
+                new_organisms.push(child);
+            }
+        }
+        // food is enriched by whatever nutrients are in the soil
+        if let Some(mut food) = intents[i].food.take() {
+            if max_blocks > food_count {
+                let uptake = sim_world.take_from_field(FieldKind::Nutrients, food.x, food.y, food.z, SOIL_UPTAKE as f32);
+                food.nutrition = food.nutrition.saturating_add(uptake as u8);
+                record(events, || Event::Produced(food.clone()));
+                sim_world.place_block(&food);
+                blocks.push(food);
+                food_count += 1;
+            }
+        }
+    }
+
+    // what only affects the organism itself, then housekeeping,
+    // with everyone reading the fields as they were before anyone warmed them
+    for (i, (intent, rng)) in intents.iter_mut().zip(rngs.iter_mut()).enumerate() {
+        population.health[i] = population.health[i].saturating_sub((hits[i] * ATTACK_DAMAGE as usize).min(u8::MAX as usize) as u8);
+        if let Some(movement) = intent.movement.take() {
+            let (id, position) = (population.ids[i], (movement.x, movement.y, movement.z));
+            let turned = movement.cells != population.cells(i);
+            if position != population.positions[i] || turned {
+                record(events, || Event::Moved { id, x: movement.x, y: movement.y, z: movement.z, cells: turned.then(|| movement.cells.clone()) });
+            }
+            population.set_position(i, position);
+            population.set_cells(i, movement.cells);
+        }
+        if intent.mutate {
+            let mut cells = population.cells(i).to_vec();
+            mutate_cells(&mut cells, rng);
+            if claims.is_free_for(i, population.positions[i], &cells) {
+                claims.give_up(i, population.positions[i], population.cells(i));
+                claims.take(i, population.positions[i], &cells);
+                record(events, || Event::Mutated { id: population.ids[i], cells: cells.clone() });
+                population.set_cells(i, cells);
+            }
+        }
+    }
+    let world = &*sim_world;
+    let strain: Vec<(bool, bool)> = (0..population.len()).into_par_iter().map(|i| { // (uncomfortable, poisoned)
+        let organism = population.view(i);
+        let temperature = organism.sample_field(world, FieldKind::Temperature);
+        ((temperature - 0.5).abs() > COMFORT_RANGE, organism.sample_field(world, FieldKind::Toxins) > TOXIN_DAMAGE_THRESHOLD)
+    }).collect();
+    record(events, || Event::Aged);
+    for (i, (uncomfortable, poisoned)) in strain.into_iter().enumerate() {
+        population.lifespan[i] = population.lifespan[i].saturating_sub(1);
+        population.ages[i] += 1;
+        population.energy[i] = population.energy[i].saturating_sub(ENERGY_PER_TICK + uncomfortable as u8);
+        if uncomfortable {
+            record(events, || Event::Uncomfortable { id: population.ids[i] });
+        }
+        if poisoned {
+            population.health[i] = population.health[i].saturating_sub(1);
+            record(events, || Event::Sickened { id: population.ids[i] });
+        }
+    }
+    for &i in &order {
+        let organism = population.view(i);
+        for (x, y, z) in organism.cells.iter().filter_map(|cell| cell_position((organism.x, organism.y, organism.z), cell)) {
+            sim_world.add_to_field(FieldKind::Temperature, x, y, z, HEAT_PER_CELL);
+        }
+        if organism.is_dead() {
+            // println!("Organism died");
+            let mut carcass = 0;
+            for val in organism.kill() {
+                if food_count < max_blocks {
+                    sim_world.place_block(&val);
+                    blocks.push(val);       // Add the dead organism's cells as food blocks
+                    food_count += 1;
+                    carcass += 1;
+                }
+            }
+            let cause = if organism.health == 0 && hits[i] > 0 {
+                Cause::Killed
+            } else if organism.health == 0 {
+                Cause::Poisoned
+            } else if organism.energy == 0 {
+                Cause::Starved
+            } else {
+                Cause::OldAge
+            };
+            record(events, || Event::Died { id: organism.id, cause, carcass });
+        }
+    }
+
+    // food decay
+    for block in blocks.iter_mut() {
+        let lost = block.decay();
+        if FOOD_DECAYS_INTO_SOIL && lost > 0 {
+            sim_world.add_to_field(FieldKind::Nutrients, block.x, block.y, block.z, lost as f32);
+        }
+        if lost > 0 && block.block_type == BlockType::Food(FoodSource::Carcass) {
+            sim_world.add_to_field(FieldKind::Toxins, block.x, block.y, block.z, TOXIN_PER_CARCASS_DECAY);
+        }
+    }
+    blocks.retain(|block| {
+        let rotten = block.is_rotten();
+        if rotten {
+            sim_world.remove_block(block);
+        }
+        !rotten
+    });
+    sim_world.update_fields();
+
+    population.retain(|organism| !organism.is_dead()); // Remove dead organisms
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum Command {
+    Spawn { definition: Option<OrganismDefinition>, x: i8, y: i8, z: i8 }, // a random organism if there's no definition
+    PlaceFood { x: i8, y: i8, z: i8 },
+    PlaceWall { x: i8, y: i8, z: i8 },
+    RemoveRegion { min: (i8, i8, i8), max: (i8, i8, i8) }, // removes organisms and blocks inside the box, inclusive
+    Meteor { x: i8, y: i8, z: i8, radius: u8 },            // wipes out everything in a sphere and heats it up
+    Cull { fraction: f32 },                                // kills a random fraction of organisms, leaving carcasses
+}
+
+impl Command {
+    // one command per line: "<tick> <command> <args>", e.g. "100 meteor 0 0 0 8" or "0 spawn hunter 5 0 0"
+    pub fn parse(line: &str) -> Result<(u64, Command), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() < 2 {
+            return Err(format!("expected a tick and a command: {}", line));
+        }
+        let tick = words[0].parse::<u64>().map_err(|_| format!("bad tick: {}", words[0]))?;
+        let args = &words[2..];
+        let number = |i: usize| -> Result<i8, String> {
+            args.get(i).ok_or(format!("missing argument in: {}", line))?
+                .parse::<i8>().map_err(|_| format!("bad number in: {}", line))
+        };
+        let command = match words[1] {
+            "spawn" => {
+                let name = args.first().ok_or(format!("missing organism in: {}", line))?;
+                let definition = match *name {
+                    "random" => None,
+                    _ => Some(OrganismDefinition::preset(name).ok_or(format!("unknown preset: {}", name))?),
+                };
+                Command::Spawn { definition, x: number(1)?, y: number(2)?, z: number(3)? }
+            }
+            "food" => Command::PlaceFood { x: number(0)?, y: number(1)?, z: number(2)? },
+            "wall" => Command::PlaceWall { x: number(0)?, y: number(1)?, z: number(2)? },
+            "remove" => Command::RemoveRegion {
+                min: (number(0)?, number(1)?, number(2)?),
+                max: (number(3)?, number(4)?, number(5)?),
+            },
+            "meteor" => Command::Meteor { x: number(0)?, y: number(1)?, z: number(2)?, radius: number(3)?.max(0) as u8 },
+            "cull" => {
+                let fraction = args.first().ok_or(format!("missing fraction in: {}", line))?
+                    .parse::<f32>().map_err(|_| format!("bad fraction in: {}", line))?;
+                Command::Cull { fraction }
+            }
+            other => return Err(format!("unknown command: {}", other)),
+        };
+        Ok((tick, command))
+    }
+    pub fn parse_script(text: &str) -> Result<Vec<(u64, Command)>, String> { // blank lines and lines starting with # are skipped
+        text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Command::parse)
+            .collect()
+    }
+}
+
+// a clone steps exactly like the original, so a copy of one is as good as a saved run
+#[derive(Clone)]
+pub struct Simulation {
+    pub population: Population,
+    pub blocks: Vec<Block>,
+    pub world: World,
+    pub tick: u64,
+    pub revision: u64, // bumped on every change, so viewers know when to redraw
+    pub seed: u64,     // the same seed, starting state and commands give the same run
+    rng: StdRng,       // for commands; the tick seeds its own generators from the seed
+    next_id: u64,      // ids come from here rather than the counter every Organism::new uses, which copies would share
+    events: Option<Vec<Event>>, // what happened since they were last taken, when they're being recorded
+    scheduled: Vec<(u64, Command)>,
+}
+
+impl Simulation {
+    pub fn new(world: World, organisms: Vec<Organism>, blocks: Vec<Block>, seed: u64) -> Simulation {
+        let next_id = organisms.iter().map(|organism| organism.id + 1).max().unwrap_or(0);
+        let mut simulation = Simulation {
+            population: organisms.into_iter().collect(),
+            blocks: Vec::new(),
+            world,
+            tick: 0,
+            revision: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            next_id,
+            events: None,
+            scheduled: Vec::new(),
+        };
+        for block in blocks {
+            simulation.add_block(block);
+        }
+        simulation
+    }
+    pub fn step(&mut self) { // runs the commands due this tick, then one tick of the world
+        let due: Vec<Command> = {
+            let tick = self.tick;
+            let (due, later) = self.scheduled.drain(..).partition(|(at, _)| *at <= tick);
+            self.scheduled = later;
+            due.into_iter().map(|(_, command)| command).collect()
+        };
+        for command in due {
+            self.apply(command);
+        }
+
+        let mut outcome = Outcome { newborns: Vec::new(), events: self.events.take() };
+        update_world(&mut self.population, &mut outcome, &mut self.blocks, MAX_ORGANISMS, MAX_BLOCKS, &mut self.world, self.seed ^ self.tick.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        self.events = outcome.events;
+        for mut organism in outcome.newborns { // the only place newborns join, after everyone alive has had their turn
+            organism.id = self.allocate_id();
+            record(&mut self.events, || Event::Born(Box::new(organism.clone())));
+            self.population.push(organism);
+        }
+        self.tick += 1;
+        self.revision += 1;
+    }
+    pub fn record_events(&mut self) { // from now on, see take_events
+        self.events.get_or_insert_with(Vec::new);
+    }
+    pub fn take_events(&mut self) -> Vec<Event> { // what happened since last time, in order; nothing unless recording
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+    pub fn schedule(&mut self, tick: u64, command: Command) { // commands for past ticks run on the next step
+        self.scheduled.push((tick, command));
+    }
+    pub fn apply(&mut self, command: Command) {
+        self.revision += 1;
+        match command {
+            Command::Spawn { definition, x, y, z } => {
+                let mut organism = match definition {
+                    Some(definition) => definition.to_organism(),
+                    None => random_organism(&mut self.rng),
+                };
+                organism.id = self.allocate_id();
+                organism.founder = organism.id;
+                organism.x = x;
+                organism.y = y;
+                organism.z = z;
+                if !organism.hits_wall(&self.world) {
+                    record(&mut self.events, || Event::Born(Box::new(organism.clone())));
+                    self.population.push(organism);
+                }
+            }
+            Command::PlaceFood { x, y, z } => self.add_block(Block::new(BlockType::Food(FoodSource::Producer), x, y, z)),
+            Command::PlaceWall { x, y, z } => self.add_block(Block::new(BlockType::Wall, x, y, z)),
+            Command::RemoveRegion { min, max } => {
+                let inside = |x: i8, y: i8, z: i8| {
+                    x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1 && z >= min.2 && z <= max.2
+                };
+                let events = &mut self.events;
+                self.population.retain(|organism| {
+                    let removed = inside(organism.x, organism.y, organism.z);
+                    if removed {
+                        record(events, || Event::Died { id: organism.id, cause: Cause::Removed, carcass: 0 });
+                    }
+                    !removed
+                });
+                self.remove_blocks(|block| inside(block.x, block.y, block.z));
+            }
+            Command::Meteor { x, y, z, radius } => {
+                let radius = radius as i32;
+                let inside = |bx: i8, by: i8, bz: i8| {
+                    let (dx, dy, dz) = (bx as i32 - x as i32, by as i32 - y as i32, bz as i32 - z as i32);
+                    dx * dx + dy * dy + dz * dz <= radius * radius
+                };
+                let events = &mut self.events;
+                self.population.retain(|organism| {
+                    let hit = organism.cells.iter().any(|cell| inside(organism.x + cell.local_x, organism.y + cell.local_y, organism.z + cell.local_z));
+                    if hit {
+                        record(events, || Event::Died { id: organism.id, cause: Cause::Removed, carcass: 0 });
+                    }
+                    !hit
+                });
+                self.remove_blocks(|block| inside(block.x, block.y, block.z));
+                self.world.add_to_field(FieldKind::Temperature, x, y, z, radius as f32);
+            }
+            Command::Cull { fraction } => {
+                let (rng, events) = (&mut self.rng, &mut self.events);
+                let mut carcasses = Vec::new();
+                self.population.retain(|organism| {
+                    let survives = rng.gen_range(0.0..1.0) >= fraction;
+                    if !survives {
+                        carcasses.extend(organism.kill());
+                        record(events, || Event::Died { id: organism.id, cause: Cause::Culled, carcass: organism.cells.len() });
+                    }
+                    survives
+                });
+                for carcass in &carcasses {
+                    self.world.place_block(carcass);
+                }
+                self.blocks.extend(carcasses);
+            }
+        }
+    }
+    fn allocate_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+    fn add_block(&mut self, block: Block) {
+        self.world.place_block(&block);
+        record(&mut self.events, || Event::Placed(block.clone()));
+        self.blocks.push(block);
+    }
+    fn remove_blocks(&mut self, should_remove: impl Fn(&Block) -> bool) {
+        let (world, events) = (&mut self.world, &mut self.events);
+        let mut kept = 0; // the blocks before this one that stay, which is where it is once the ones before it are gone
+        self.blocks.retain(|block| {
+            if !should_remove(block) {
+                kept += 1;
+                return true;
+            }
+            world.remove_block(block);
+            record(events, || Event::Unplaced { block: kept });
+            false
+        });
+    }
+}
+
+pub fn random_organism(rng: &mut impl Rng) -> Organism {
+    let mut organism = Organism::new();
+    for _ in 0..rng.gen_range(1..6) {
+        organism.add_random_cell(rng);
+    }
+    for cell in &mut organism.cells {
+        if let CellType::Brain(brain) = &mut cell.cell_type {
+            brain.aggression = rng.gen_range(0.0..1.0);
+            brain.hunger = rng.gen_range(0.0..1.0);
+        }
+    }
+    organism
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Snapshot;
+
+    fn seeded(seed: u64) -> Simulation { // founders come from commands, so both copies give out the same ids
+        let mut sim = Simulation::new(World::new(64, 64, 64), Vec::new(), Vec::new(), seed);
+        for (i, name) in ["blob", "grazer", "hunter", "plant"].into_iter().enumerate() {
+            let definition = OrganismDefinition::preset(name);
+            sim.schedule(0, Command::Spawn { definition, x: i as i8 * 6, y: 0, z: 0 });
+        }
+        sim.schedule(0, Command::Spawn { definition: None, x: -6, y: 0, z: 0 });
+        sim
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let (mut first, mut second) = (seeded(7), seeded(7));
+        for _ in 0..200 {
+            first.step();
+            second.step();
+            assert_eq!(Snapshot::of(&first).to_ron().unwrap(), Snapshot::of(&second).to_ron().unwrap(), "tick {}", first.tick);
+        }
+        assert!(first.population.len() > 5, "nothing happened to compare");
+    }
+
+    #[test]
+    fn bodies_never_overlap() {
+        let mut sim = seeded(9);
+        for _ in 0..300 {
+            sim.step();
+            let mut owners = HashMap::new();
+            for i in 0..sim.population.len() {
+                for voxel in Claims::voxels(sim.population.positions[i], sim.population.cells(i)) {
+                    if let Some(other) = owners.insert(voxel, sim.population.ids[i]).filter(|&other| other != sim.population.ids[i]) {
+                        panic!("tick {}: {} and {} both at {:?}", sim.tick, other, sim.population.ids[i], voxel);
+                    }
+                }
+            }
+        }
+        assert!(sim.population.len() > 5, "nothing happened to compare");
+    }
+
+    #[test]
+    fn organisms_stay_in_the_world() { // the default run, in a small world so they reach its edges sooner
+        let blob = OrganismDefinition::preset("blob").unwrap().to_organism();
+        let mut sim = Simulation::new(World::new(32, 32, 32), vec![blob], Vec::new(), 42);
+        for _ in 0..5000 {
+            sim.step();
+            for &(x, y, z) in &sim.population.positions {
+                assert!(sim.world.to_grid(x, y, z).is_some(), "tick {}: an organism at {}, {}, {}", sim.tick, x, y, z);
+            }
+        }
+        assert!(!sim.population.is_empty(), "everyone died before the edges were tested");
+    }
+
+    #[test]
+    fn every_command_parses() {
+        let parsed = |line: &str| Command::parse(line).unwrap();
+        assert!(matches!(parsed("0 spawn hunter 5 0 -3"), (0, Command::Spawn { definition: Some(_), x: 5, y: 0, z: -3 })));
+        assert!(matches!(parsed("7 spawn random 1 2 3"), (7, Command::Spawn { definition: None, x: 1, y: 2, z: 3 })));
+        assert!(matches!(parsed("10 food 1 -2 3"), (10, Command::PlaceFood { x: 1, y: -2, z: 3 })));
+        assert!(matches!(parsed("10 wall 4 5 6"), (10, Command::PlaceWall { x: 4, y: 5, z: 6 })));
+        assert!(matches!(parsed("20 remove -1 -2 -3 4 5 6"), (20, Command::RemoveRegion { min: (-1, -2, -3), max: (4, 5, 6) })));
+        assert!(matches!(parsed("100 meteor 0 0 0 8"), (100, Command::Meteor { x: 0, y: 0, z: 0, radius: 8 })));
+        assert!(matches!(parsed("100 meteor 0 0 0 -8"), (100, Command::Meteor { radius: 0, .. }))); // a negative radius is none
+        assert!(matches!(parsed("50 cull 0.25"), (50, Command::Cull { fraction }) if fraction == 0.25));
+        assert!(matches!(parsed("  3   food  0 0 0  "), (3, Command::PlaceFood { .. }))); // any whitespace between words
+    }
+
+    #[test]
+    fn scripts_skip_comments_and_blank_lines() {
+        let script = "# a meteor, then some food\n\n100 meteor 0 0 0 8\n   \n  # indented comment\n110 food 1 2 3\n";
+        let commands = Command::parse_script(script).unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(commands[0], (100, Command::Meteor { .. })));
+        assert!(matches!(commands[1], (110, Command::PlaceFood { .. })));
+        assert!(Command::parse_script("1 food 0 0 0\n2 explode 0 0 0\n").is_err()); // one bad line spoils the script
+    }
+
+    #[test]
+    fn bad_commands_are_errors() {
+        for line in [
+            "",                       // nothing at all
+            "100",                    // no command
+            "soon food 0 0 0",        // the tick isn't a number
+            "-1 food 0 0 0",          // nor is a negative one
+            "100 explode 0 0 0",      // unknown command
+            "100 spawn",              // no organism
+            "100 spawn dragon 0 0 0", // unknown preset
+            "100 spawn hunter 0 0",   // missing coordinate
+            "100 food 1 2",           // missing coordinate
+            "100 food 1 two 3",       // not a number
+            "100 wall 1 2 300",       // doesn't fit the world's coordinates
+            "100 remove 0 0 0 1 1",   // missing corner coordinate
+            "100 meteor 0 0 0",       // missing radius
+            "100 cull",               // missing fraction
+            "100 cull half",          // not a fraction
+        ] {
+            assert!(Command::parse(line).is_err(), "{:?} parsed", line);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+// 2D cross sections through the world, for the viewer, pictures and text
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use kiss3d::nalgebra::{Point2, Point3, Translation2, Vector2};
+use kiss3d::resource::PlanarMesh;
+use kiss3d::scene::PlanarSceneNode;
+use kiss3d::window::Window;
+use crate::block::{BlockType, FoodSource};
+use crate::cell::CellType;
+use crate::raster::Image;
+use crate::snapshot::Snapshot;
+
+static PANEL_PIXELS_PER_VOXEL: f32 = 3.0;
+static PANEL_MARGIN: f32 = 10.0;
+static PANEL_BACKGROUND: (f32, f32, f32) = (0.05, 0.05, 0.1);
+static MAX_PANEL_VERTICES: usize = 65532; // planar meshes index vertices with u16 too
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn next(self) -> Axis {
+        match self {
+            Axis::X => Axis::Y,
+            Axis::Y => Axis::Z,
+            Axis::Z => Axis::X,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, Copy)]
+pub enum Content {
+    Empty,
+    Wall,
+    Food(FoodSource),
+    Cell(usize), // index into CellType::all()
+}
+
+impl Content {
+    pub fn color(&self) -> Option<(f32, f32, f32)> {
+        match self {
+            Content::Empty => None,
+            Content::Wall => Some(BlockType::Wall.color()),
+            Content::Food(source) => Some(BlockType::Food(*source).color()),
+            Content::Cell(i) => Some(CellType::all()[*i].color()),
+        }
+    }
+    pub fn symbol(&self) -> char {
+        match self {
+            Content::Empty => '.',
+            Content::Wall => '#',
+            Content::Food(FoodSource::Producer) => ',',
+            Content::Food(FoodSource::Carcass) => '%',
+            Content::Cell(i) => ['B', 'o', 'A', 'K', 'E', 'M', 'P'][*i], // brain, eye, armor, killer, eater, mover, producer
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Slice {
+    pub axis: Axis,
+    pub position: Option<i16>,  // world coordinate of the plane along the axis, None for a projection
+    pub width: usize,           // columns
+    pub height: usize,          // rows, the first at the top
+    pub contents: Vec<Content>, // row by row
+}
+
+impl Slice {
+    // the plane through the world at position along axis; vertical planes have y going up,
+    // the horizontal plane is seen from above with x to the right
+    pub fn take(sim: &Snapshot, axis: Axis, position: i16) -> Slice {
+        let (width, height) = match axis {
+            Axis::X => (sim.depth, sim.height),
+            Axis::Y => (sim.width, sim.depth),
+            Axis::Z => (sim.width, sim.height),
+        };
+        let (half_x, half_y, half_z) = ((sim.width / 2) as i16, (sim.height / 2) as i16, (sim.depth / 2) as i16);
+        let to_world = |column: usize, row: usize| -> (i16, i16, i16) {
+            let (column, row) = (column as i16, row as i16);
+            match axis {
+                Axis::X => (position, half_y - 1 - row, column - half_z),
+                Axis::Y => (column - half_x, position, row - half_z),
+                Axis::Z => (column - half_x, half_y - 1 - row, position),
+            }
+        };
+        let on_plane = |x: i16, y: i16, z: i16| match axis {
+            Axis::X => x == position,
+            Axis::Y => y == position,
+            Axis::Z => z == position,
+        };
+
+        let mut overlay = HashMap::new();
+        for block in &sim.blocks {
+            if on_plane(block.x as i16, block.y as i16, block.z as i16) {
+                let content = match block.block_type {
+                    BlockType::Wall => Content::Wall,
+                    BlockType::Food(source) => Content::Food(source),
+                };
+                overlay.insert((block.x as i16, block.y as i16, block.z as i16), content);
+            }
+        }
+        for organism in &sim.organisms {
+            for cell in &organism.cells {
+                let (x, y, z) = (organism.x as i16 + cell.local_x as i16, organism.y as i16 + cell.local_y as i16, organism.z as i16 + cell.local_z as i16);
+                if on_plane(x, y, z) {
+                    overlay.insert((x, y, z), Content::Cell(cell.cell_type.index()));
+                }
+            }
+        }
+
+        let mut contents = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for column in 0..width {
+                let (x, y, z) = to_world(column, row);
+                let content = overlay.get(&(x, y, z)).copied().unwrap_or(Content::Empty);
+                contents.push(content);
+            }
+        }
+        Slice { axis, position: Some(position), width, height, contents }
+    }
+
+    // the whole world flattened along axis, showing whatever is nearest to a viewer on the positive side
+    pub fn project(sim: &Snapshot, axis: Axis) -> Slice {
+        let (width, height) = match axis {
+            Axis::X => (sim.depth, sim.height),
+            Axis::Y => (sim.width, sim.depth),
+            Axis::Z => (sim.width, sim.height),
+        };
+        let (half_x, half_y, half_z) = ((sim.width / 2) as i16, (sim.height / 2) as i16, (sim.depth / 2) as i16);
+        // the same layout as take(), plus how far along the axis the voxel is
+        let to_slice = |x: i16, y: i16, z: i16| -> Option<(usize, i16)> {
+            let (column, row, depth) = match axis {
+                Axis::X => (z + half_z, half_y - 1 - y, x),
+                Axis::Y => (x + half_x, z + half_z, y),
+                Axis::Z => (x + half_x, half_y - 1 - y, z),
+            };
+            if column < 0 || row < 0 || column as usize >= width || row as usize >= height {
+                return None;
+            }
+            Some((row as usize * width + column as usize, depth))
+        };
+        let mut contents = vec![Content::Empty; width * height];
+        let mut nearest = vec![i16::MIN; width * height];
+        let mut show = |x: i16, y: i16, z: i16, content: Content| {
+            if let Some((i, depth)) = to_slice(x, y, z) {
+                if depth >= nearest[i] { // ties go to whatever comes later, so cells beat blocks
+                    nearest[i] = depth;
+                    contents[i] = content;
+                }
+            }
+        };
+        for block in &sim.blocks {
+            let content = match block.block_type {
+                BlockType::Wall => Content::Wall,
+                BlockType::Food(source) => Content::Food(source),
+            };
+            show(block.x as i16, block.y as i16, block.z as i16, content);
+        }
+        for organism in &sim.organisms {
+            for cell in &organism.cells {
+                show(organism.x as i16 + cell.local_x as i16, organism.y as i16 + cell.local_y as i16, organism.z as i16 + cell.local_z as i16,
+                     Content::Cell(cell.cell_type.index()));
+            }
+        }
+        Slice { axis, position: None, width, height, contents }
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> Content {
+        self.contents[row * self.width + column]
+    }
+
+    pub fn to_ascii(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for row in 0..self.height {
+            text.extend((0..self.width).map(|column| self.get(column, row).symbol()));
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn to_image(&self, scale: u32) -> Image { // each voxel becomes a scale x scale square
+        let scale = scale.max(1) as usize;
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut pixels = vec![0; width * height * 3];
+        for (i, pixel) in pixels.chunks_mut(3).enumerate() {
+            let (column, row) = ((i % width) / scale, (i / width) / scale);
+            let (r, g, b) = self.get(column, row).color().unwrap_or(PANEL_BACKGROUND);
+            pixel.copy_from_slice(&[(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8]);
+        }
+        Image { width: width as u32, height: height as u32, pixels }
+    }
+}
+
+pub struct SlicePanel { // the current slice drawn in a corner of the viewer, one mesh per color
+    pub axis: Axis,
+    pub position: i16,
+    pub visible: bool,
+    root: Option<PlanarSceneNode>,
+    shown: Option<Slice>,
+}
+
+impl Default for SlicePanel {
+    fn default() -> SlicePanel {
+        SlicePanel::new()
+    }
+}
+
+impl SlicePanel {
+    pub fn new() -> SlicePanel {
+        SlicePanel { axis: Axis::Y, position: 0, visible: false, root: None, shown: None }
+    }
+
+    pub fn move_by(&mut self, delta: i16) {
+        self.position = (self.position + delta).clamp(i8::MIN as i16, i8::MAX as i16);
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{:?} = {}", self.axis, self.position).to_lowercase()
+    }
+
+    pub fn sync(&mut self, window: &mut Window, sim: &Snapshot) {
+        if !self.visible {
+            if let Some(mut root) = self.root.take() {
+                window.remove_planar_node(&mut root);
+            }
+            self.shown = None;
+            return;
+        }
+        let slice = Slice::take(sim, self.axis, self.position);
+        let changed = self.shown.as_ref().is_none_or(|shown| shown.axis != slice.axis || shown.position != slice.position || shown.contents != slice.contents);
+        if changed {
+            if let Some(mut root) = self.root.take() {
+                window.remove_planar_node(&mut root);
+            }
+            self.root = Some(build_panel(window, &slice));
+            self.shown = Some(slice);
+        }
+        if let (Some(root), Some(shown)) = (&mut self.root, &self.shown) {
+            // planar coordinates are logical pixels with the origin in the middle of the window
+            let scale = window.scale_factor() as f32;
+            let (half_width, half_height) = (window.width() as f32 / scale / 2.0, window.height() as f32 / scale / 2.0);
+            let panel_height = shown.height as f32 * PANEL_PIXELS_PER_VOXEL;
+            root.set_local_translation(Translation2::new(-half_width + PANEL_MARGIN, -half_height + PANEL_MARGIN + panel_height));
+        }
+    }
+
+    pub fn draw_plane(&self, window: &mut Window, sim: &Snapshot) { // outlines the sliced plane in the 3D view
+        if !self.visible {
+            return;
+        }
+        let (hx, hy, hz) = ((sim.width / 2) as f32, (sim.height / 2) as f32, (sim.depth / 2) as f32);
+        let p = self.position as f32;
+        let corners = match self.axis {
+            Axis::X => [Point3::new(p, -hy, -hz), Point3::new(p, hy, -hz), Point3::new(p, hy, hz), Point3::new(p, -hy, hz)],
+            Axis::Y => [Point3::new(-hx, p, -hz), Point3::new(hx, p, -hz), Point3::new(hx, p, hz), Point3::new(-hx, p, hz)],
+            Axis::Z => [Point3::new(-hx, -hy, p), Point3::new(hx, -hy, p), Point3::new(hx, hy, p), Point3::new(-hx, hy, p)],
+        };
+        for i in 0..4 {
+            window.draw_line(&corners[i], &corners[(i + 1) % 4], &Point3::new(0.0, 1.0, 1.0));
+        }
+    }
+}
+
+// a planar group with its top left corner at the origin, growing right and down
+fn build_panel(window: &mut Window, slice: &Slice) -> PlanarSceneNode {
+    let mut root = window.add_planar_group();
+    let size = PANEL_PIXELS_PER_VOXEL;
+    let mut background = root.add_rectangle(slice.width as f32 * size, slice.height as f32 * size);
+    background.set_local_translation(Translation2::new(slice.width as f32 * size / 2.0, -(slice.height as f32) * size / 2.0));
+    let (r, g, b) = PANEL_BACKGROUND;
+    background.set_color(r, g, b);
+
+    let mut by_content: HashMap<Content, Vec<(usize, usize)>> = HashMap::new();
+    for row in 0..slice.height {
+        for column in 0..slice.width {
+            let content = slice.get(column, row);
+            if content != Content::Empty {
+                by_content.entry(content).or_default().push((column, row));
+            }
+        }
+    }
+    for (content, squares) in by_content {
+        let (r, g, b) = content.color().unwrap();
+        for chunk in squares.chunks(MAX_PANEL_VERTICES / 4) {
+            let mut coords = Vec::with_capacity(chunk.len() * 4);
+            let mut faces = Vec::with_capacity(chunk.len() * 2);
+            for &(column, row) in chunk {
+                let (left, top) = (column as f32 * size, -(row as f32) * size);
+                let first = coords.len() as u16;
+                coords.extend([Point2::new(left, top), Point2::new(left + size, top), Point2::new(left + size, top - size), Point2::new(left, top - size)]);
+                faces.push(Point3::new(first, first + 1, first + 2));
+                faces.push(Point3::new(first, first + 2, first + 3));
+            }
+            let mut node = root.add_mesh(Rc::new(RefCell::new(PlanarMesh::new(coords, faces, None, false))), Vector2::new(1.0, 1.0));
+            node.set_color(r, g, b);
+        }
+    }
+    root
+}
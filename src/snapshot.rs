@@ -0,0 +1,53 @@
+// immutable copies of the simulation, published by the tick thread for the frontends to read
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use crate::block::{Block, BlockType};
+use crate::organism::Organism;
+use crate::simulation::Simulation;
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub revision: u64, // the simulation's revision when this was taken
+    pub organisms: Vec<Organism>,
+    pub blocks: Vec<Block>,
+    pub width: usize,  // size of the world
+    pub height: usize,
+    pub depth: usize,
+}
+
+impl Snapshot {
+    pub fn of(sim: &Simulation) -> Snapshot {
+        Snapshot {
+            tick: sim.tick,
+            revision: sim.revision,
+            organisms: sim.population.to_organisms(),
+            blocks: sim.blocks.clone(),
+            width: sim.world.width,
+            height: sim.world.height,
+            depth: sim.world.depth,
+        }
+    }
+    pub fn organism(&self, id: u64) -> Option<&Organism> {
+        self.organisms.iter().find(|organism| organism.id == id)
+    }
+    pub fn walls(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter().filter(|block| block.block_type == BlockType::Wall)
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        ron::ser::to_string(self).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+    }
+    pub fn from_ron(text: &str) -> Result<Snapshot> {
+        ron::from_str(text).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+    }
+    pub fn save(&self, path: &str) -> Result<()> {
+        fs::write(path, self.to_ron()?)
+    }
+    pub fn load(path: &str) -> Result<Snapshot> {
+        Snapshot::from_ron(&fs::read_to_string(path)?)
+    }
+}
@@ -0,0 +1,396 @@
+// a spatial hash, so finding what's near something doesn't mean looking at everything
+
+use std::collections::HashMap;
+use crate::block::{Block, BlockType};
+use crate::population::Population;
+
+static BUCKET_SIZE: i32 = 8; // voxels along each side of a bucket
+
+pub type Position = (i32, i32, i32);
+
+fn bucket_of((x, y, z): Position) -> Position {
+    (x.div_euclid(BUCKET_SIZE), y.div_euclid(BUCKET_SIZE), z.div_euclid(BUCKET_SIZE))
+}
+
+fn distance_squared(a: Position, b: Position) -> i32 {
+    let (dx, dy, dz) = (a.0 - b.0, a.1 - b.1, a.2 - b.2);
+    dx * dx + dy * dy + dz * dz
+}
+
+// entries are whatever identifies a thing to the caller, usually its index in a list;
+// queries give entries back in a fixed order for a given sequence of inserts, so they're safe to use in the tick
+#[derive(Clone)]
+pub struct SpatialIndex<T> {
+    buckets: HashMap<Position, Vec<(T, Position)>>,
+}
+
+impl<T> Default for SpatialIndex<T> {
+    fn default() -> SpatialIndex<T> {
+        SpatialIndex { buckets: HashMap::new() }
+    }
+}
+
+impl<T: Copy + PartialEq> SpatialIndex<T> {
+    pub fn new() -> SpatialIndex<T> {
+        SpatialIndex::default()
+    }
+    pub fn of(entries: impl IntoIterator<Item = (T, Position)>) -> SpatialIndex<T> {
+        let mut index = SpatialIndex::new();
+        for (entry, position) in entries {
+            index.insert(entry, position);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, entry: T, position: Position) {
+        self.buckets.entry(bucket_of(position)).or_default().push((entry, position));
+    }
+    pub fn remove(&mut self, entry: T, position: Position) -> bool {
+        let bucket = bucket_of(position);
+        let Some(entries) = self.buckets.get_mut(&bucket) else {
+            return false;
+        };
+        let Some(i) = entries.iter().position(|&(e, p)| e == entry && p == position) else {
+            return false;
+        };
+        entries.remove(i); // not swap_remove, which would change the order queries see
+        if entries.is_empty() {
+            self.buckets.remove(&bucket);
+        }
+        true
+    }
+    pub fn relocate(&mut self, entry: T, from: Position, to: Position) { // for things that moved
+        if self.remove(entry, from) {
+            self.insert(entry, to);
+        }
+    }
+
+    pub fn at(&self, position: Position) -> impl Iterator<Item = T> + '_ {
+        self.buckets.get(&bucket_of(position)).into_iter().flatten()
+            .filter(move |&&(_, p)| p == position)
+            .map(|&(entry, _)| entry)
+    }
+
+    // everything in the cube reaching radius voxels out from position on every axis
+    pub fn within(&self, position: Position, radius: i32) -> Vec<T> {
+        let (low, high) = (bucket_of((position.0 - radius, position.1 - radius, position.2 - radius)),
+                           bucket_of((position.0 + radius, position.1 + radius, position.2 + radius)));
+        let mut found = Vec::new();
+        for bx in low.0..=high.0 {
+            for by in low.1..=high.1 {
+                for bz in low.2..=high.2 {
+                    let Some(entries) = self.buckets.get(&(bx, by, bz)) else { continue };
+                    found.extend(entries.iter()
+                        .filter(|(_, p)| (p.0 - position.0).abs() <= radius && (p.1 - position.1).abs() <= radius && (p.2 - position.2).abs() <= radius)
+                        .map(|&(entry, _)| entry));
+                }
+            }
+        }
+        found
+    }
+
+    // the closest accepted entry no further than max_distance, searching outwards a ring of buckets at a time
+    pub fn nearest(&self, position: Position, max_distance: i32, accept: impl Fn(T) -> bool) -> Option<T> {
+        let center = bucket_of(position);
+        let mut best: Option<(i32, T)> = None;
+        for ring in 0..=(max_distance / BUCKET_SIZE + 1) {
+            for bx in center.0 - ring..=center.0 + ring {
+                for by in center.1 - ring..=center.1 + ring {
+                    for bz in center.2 - ring..=center.2 + ring {
+                        let on_ring = (bx - center.0).abs() == ring || (by - center.1).abs() == ring || (bz - center.2).abs() == ring;
+                        let Some(entries) = self.buckets.get(&(bx, by, bz)).filter(|_| on_ring) else { continue };
+                        for &(entry, p) in entries {
+                            let distance = distance_squared(position, p);
+                            if distance <= max_distance * max_distance && best.is_none_or(|(d, _)| distance < d) && accept(entry) {
+                                best = Some((distance, entry));
+                            }
+                        }
+                    }
+                }
+            }
+            // everything in the next ring is at least this far away
+            if best.is_some_and(|(d, _)| d <= (ring * BUCKET_SIZE) * (ring * BUCKET_SIZE)) {
+                break;
+            }
+        }
+        best.map(|(_, entry)| entry)
+    }
+
+    // the first entry in the first occupied voxel a ray passes through; entries sharing a voxel come back in the order they went in
+    pub fn ray(&self, origin: [f32; 3], direction: [f32; 3], max_voxels: usize) -> Option<T> {
+        // voxels are unit cubes centered on integer coordinates, shift by half so flooring finds them
+        let start = [origin[0] + 0.5, origin[1] + 0.5, origin[2] + 0.5];
+        let mut voxel = [start[0].floor() as i32, start[1].floor() as i32, start[2].floor() as i32];
+        let mut step = [0; 3];
+        let mut next = [f32::INFINITY; 3];  // distance along the ray to the next boundary on each axis
+        let mut delta = [f32::INFINITY; 3]; // distance along the ray between boundaries on each axis
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                delta[axis] = 1.0 / direction[axis];
+                next[axis] = (voxel[axis] as f32 + 1.0 - start[axis]) * delta[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                delta[axis] = -1.0 / direction[axis];
+                next[axis] = (start[axis] - voxel[axis] as f32) * delta[axis];
+            }
+        }
+        for _ in 0..max_voxels {
+            if let Some(entry) = self.at((voxel[0], voxel[1], voxel[2])).next() {
+                return Some(entry);
+            }
+            let axis = if next[0] < next[1] && next[0] < next[2] { 0 } else if next[1] < next[2] { 1 } else { 2 };
+            voxel[axis] += step[axis];
+            next[axis] += delta[axis];
+        }
+        None
+    }
+}
+
+// food per coordinate on each axis, kept as Fenwick trees so counting everything on one side of a plane takes a few lookups
+#[derive(Clone)]
+struct FoodCounts {
+    axes: [Vec<i32>; 3], // x, y, z; slots 1 to 256 are the coordinates -128 to 127, slot 0 is unused
+    total: i32,
+}
+
+impl Default for FoodCounts {
+    fn default() -> FoodCounts {
+        FoodCounts { axes: [vec![0; 257], vec![0; 257], vec![0; 257]], total: 0 }
+    }
+}
+
+impl FoodCounts {
+    fn change(&mut self, position: Position, amount: i32) {
+        for (axis, v) in [position.0, position.1, position.2].into_iter().enumerate() {
+            let mut k = (v + 129) as usize;
+            while k < 257 {
+                self.axes[axis][k] += amount;
+                k += k & k.wrapping_neg();
+            }
+        }
+        self.total += amount;
+    }
+    fn up_to(&self, axis: usize, v: i32) -> i32 { // food with this coordinate no more than v on the axis
+        let mut k = (v + 129).clamp(0, 256) as usize;
+        let mut count = 0;
+        while k > 0 {
+            count += self.axes[axis][k];
+            k -= k & k.wrapping_neg();
+        }
+        count
+    }
+}
+
+// where the food is, kept up to date as it's placed, eaten and rots; several blocks can share a voxel
+#[derive(Clone, Default)]
+pub struct FoodIndex {
+    voxels: SpatialIndex<Position>, // one entry per block, which is its own position
+    counts: FoodCounts,
+}
+
+impl FoodIndex {
+    pub fn of(blocks: &[Block]) -> FoodIndex {
+        let mut food = FoodIndex::default();
+        for block in blocks {
+            food.add(block);
+        }
+        food
+    }
+    pub fn add(&mut self, block: &Block) { // anything but food is ignored
+        if matches!(block.block_type, BlockType::Food(_)) {
+            let position = (block.x as i32, block.y as i32, block.z as i32);
+            self.voxels.insert(position, position);
+            self.counts.change(position, 1);
+        }
+    }
+    pub fn remove(&mut self, block: &Block) {
+        let position = (block.x as i32, block.y as i32, block.z as i32);
+        if matches!(block.block_type, BlockType::Food(_)) && self.voxels.remove(position, position) {
+            self.counts.change(position, -1);
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.counts.total as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.counts.total == 0
+    }
+    // voxels with food in the cube reaching radius voxels out from position, each once
+    pub fn within(&self, position: Position, radius: i32) -> Vec<Position> {
+        let mut found = self.voxels.within(position, radius);
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+    // food anywhere past position in a direction along one axis, however far
+    pub fn ahead(&self, position: Position, (dx, dy, dz): (i8, i8, i8)) -> usize {
+        let (axis, v, d) = if dx != 0 { (0, position.0, dx) } else if dy != 0 { (1, position.1, dy) } else if dz != 0 { (2, position.2, dz) } else { return 0 };
+        let count = if d > 0 { self.counts.total - self.counts.up_to(axis, v) } else { self.counts.up_to(axis, v - 1) };
+        count as usize
+    }
+    pub fn nearest(&self, position: Position, max_distance: i32) -> Option<Position> {
+        self.voxels.nearest(position, max_distance, |_| true)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Neighbour { // what an organism can tell about another one next to it
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
+    pub killers: usize, // killer cells
+}
+
+// what an organism can sense: everyone else and the food, through the indexes the simulation keeps up to date
+#[derive(Clone, Copy)]
+pub struct Surroundings<'a> {
+    pub population: &'a Population,
+    pub food: &'a FoodIndex,
+}
+
+impl<'a> Surroundings<'a> {
+    pub fn organisms_within(&self, x: i8, y: i8, z: i8, radius: i32) -> impl Iterator<Item = Neighbour> + 'a {
+        let population = self.population;
+        population.within((x, y, z), radius).into_iter().map(move |i| {
+            let (x, y, z) = population.positions[i];
+            Neighbour { x, y, z, killers: population.traits[i].killers as usize }
+        })
+    }
+    pub fn food_within(&self, x: i8, y: i8, z: i8, radius: i32) -> Vec<(i8, i8, i8)> {
+        self.food.within((x as i32, y as i32, z as i32), radius).into_iter().map(|(x, y, z)| (x as i8, y as i8, z as i8)).collect()
+    }
+    // food an eye facing (dx, dy, dz), one axis only, sees: everything ahead of it on that axis
+    pub fn food_in_sight(&self, x: i8, y: i8, z: i8, direction: (i8, i8, i8)) -> usize {
+        self.food.ahead((x as i32, y as i32, z as i32), direction)
+    }
+    pub fn nearest_food(&self, x: i8, y: i8, z: i8, max_distance: i32) -> Option<(i8, i8, i8)> {
+        self.food.nearest((x as i32, y as i32, z as i32), max_distance).map(|(x, y, z)| (x as i8, y as i8, z as i8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::block::FoodSource;
+
+    fn points(rng: &mut StdRng, count: usize, spread: i32) -> Vec<Position> { // some share a voxel, as food can
+        (0..count).map(|_| (rng.gen_range(-spread..spread), rng.gen_range(-spread..spread), rng.gen_range(-spread..spread))).collect()
+    }
+
+    #[test]
+    fn within_matches_a_scan() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let points = points(&mut rng, 400, 20);
+        let index = SpatialIndex::of(points.iter().copied().enumerate());
+        for _ in 0..200 {
+            let position = (rng.gen_range(-25..25), rng.gen_range(-25..25), rng.gen_range(-25..25));
+            let radius = rng.gen_range(0..12);
+            let mut found = index.within(position, radius);
+            found.sort_unstable();
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|&i| (points[i].0 - position.0).abs() <= radius && (points[i].1 - position.1).abs() <= radius && (points[i].2 - position.2).abs() <= radius)
+                .collect();
+            assert_eq!(found, expected, "around {:?}, radius {}", position, radius);
+        }
+    }
+
+    #[test]
+    fn nearest_matches_a_scan() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let points = points(&mut rng, 300, 40);
+        let index = SpatialIndex::of(points.iter().copied().enumerate());
+        for _ in 0..200 {
+            let position = (rng.gen_range(-50..50), rng.gen_range(-50..50), rng.gen_range(-50..50));
+            let max_distance = rng.gen_range(0..40);
+            let accept = |i: usize| !i.is_multiple_of(3);
+            // several can be just as close, so compare how far away the one found is
+            let found = index.nearest(position, max_distance, accept).map(|i| distance_squared(position, points[i]));
+            let expected = (0..points.len())
+                .filter(|&i| accept(i))
+                .map(|i| distance_squared(position, points[i]))
+                .filter(|&d| d <= max_distance * max_distance)
+                .min();
+            assert_eq!(found, expected, "around {:?}, up to {}", position, max_distance);
+        }
+    }
+
+    #[test]
+    fn ray_matches_a_scan() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let points = points(&mut rng, 200, 12);
+        let index = SpatialIndex::of(points.iter().copied().enumerate());
+        let mut checked = 0;
+        for _ in 0..300 {
+            let origin = [rng.gen_range(-15.0..15.0), rng.gen_range(-15.0..15.0), rng.gen_range(-15.0..15.0)];
+            let direction: [f32; 3] = [rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)];
+            // how far along the ray it enters and leaves each point's voxel, if it passes through at all
+            let passes: Vec<(f32, f32, usize)> = (0..points.len()).filter_map(|i| {
+                let center = [points[i].0 as f32, points[i].1 as f32, points[i].2 as f32];
+                let (mut enter, mut leave) = (0.0f32, f32::INFINITY);
+                for axis in 0..3 {
+                    let (low, high) = ((center[axis] - 0.5 - origin[axis]) / direction[axis], (center[axis] + 0.5 - origin[axis]) / direction[axis]);
+                    enter = enter.max(low.min(high));
+                    leave = leave.min(low.max(high));
+                }
+                (enter <= leave).then_some((enter, leave, i))
+            }).collect();
+            // a ray that grazes an edge, or enters two voxels at once, could fairly go either way
+            let first = passes.iter().map(|&(enter, _, _)| enter).fold(f32::INFINITY, f32::min);
+            let grazes = passes.iter().any(|&(enter, leave, _)| leave - enter < 1e-3);
+            let first_voxel = passes.iter().find(|&&(enter, _, _)| enter == first).map(|&(_, _, i)| points[i]);
+            let tied = passes.iter().any(|&(enter, _, i)| enter - first < 1e-3 && Some(points[i]) != first_voxel);
+            if grazes || tied {
+                continue;
+            }
+            // entries sharing a voxel come back in the order they went in, which is by index here
+            let expected = passes.iter().filter(|&&(enter, _, _)| enter == first).map(|&(_, _, i)| i).min();
+            assert_eq!(index.ray(origin, direction, 1000), expected, "from {:?} towards {:?}", origin, direction);
+            checked += 1;
+        }
+        assert!(checked > 250, "only {} rays were clear cut", checked);
+        assert_eq!(index.ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], 0), None); // not a single voxel to look in
+    }
+
+    #[test]
+    fn food_queries_match_a_scan() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut blocks: Vec<Block> = points(&mut rng, 500, 128).into_iter() // the whole range, since sight has no limit
+            .map(|(x, y, z)| Block::new(BlockType::Food(FoodSource::Producer), x as i8, y as i8, z as i8))
+            .collect();
+        blocks.push(Block::new(BlockType::Wall, 0, 0, 0)); // which isn't food
+        let mut food = FoodIndex::of(&blocks);
+        for block in blocks.drain(..100) { // and some of it eaten again
+            food.remove(&block);
+        }
+        blocks.pop();
+        food.remove(&Block::new(BlockType::Food(FoodSource::Carcass), 1, 2, 3)); // never there, so nothing changes
+        assert_eq!(food.len(), blocks.len());
+
+        let position_of = |block: &Block| (block.x as i32, block.y as i32, block.z as i32);
+        for _ in 0..200 {
+            let position = (rng.gen_range(-128..128), rng.gen_range(-128..128), rng.gen_range(-128..128));
+            for direction in [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)] {
+                let ahead = |p: Position| match direction {
+                    (1, _, _) => p.0 > position.0,
+                    (-1, _, _) => p.0 < position.0,
+                    (_, 1, _) => p.1 > position.1,
+                    (_, -1, _) => p.1 < position.1,
+                    (_, _, 1) => p.2 > position.2,
+                    _ => p.2 < position.2,
+                };
+                let expected = blocks.iter().filter(|block| ahead(position_of(block))).count();
+                assert_eq!(food.ahead(position, direction), expected, "from {:?} towards {:?}", position, direction);
+            }
+            let radius = rng.gen_range(0..30);
+            let mut expected: Vec<Position> = blocks.iter().map(position_of)
+                .filter(|p| (p.0 - position.0).abs() <= radius && (p.1 - position.1).abs() <= radius && (p.2 - position.2).abs() <= radius)
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+            assert_eq!(food.within(position, radius), expected, "around {:?}, radius {}", position, radius);
+        }
+    }
+}
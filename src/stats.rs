@@ -0,0 +1,95 @@
+// summary numbers about the simulation, shared by the frontends
+
+use std::collections::VecDeque;
+use std::time::Instant;
+use crate::block::BlockType;
+use crate::snapshot::Snapshot;
+
+pub const CELL_TYPE_COUNT: usize = 7; // length of CellType::all()
+static RATE_SAMPLE_SECONDS: f32 = 0.5; // rates are averaged over this long
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Stats {
+    pub tick: u64,
+    pub population: usize,
+    pub food: usize,
+    pub walls: usize,
+    pub cells: [usize; CELL_TYPE_COUNT], // totals over all organisms, indexed by CellType::index()
+    pub mean_aggression: f32,
+    pub mean_hunger: f32,
+}
+
+impl Stats {
+    pub fn collect(sim: &Snapshot) -> Stats {
+        let mut cells = [0; CELL_TYPE_COUNT];
+        for cell in sim.organisms.iter().flat_map(|organism| organism.cells.iter()) {
+            cells[cell.cell_type.index()] += 1;
+        }
+        let brains: Vec<(f32, f32)> = sim.organisms.iter()
+            .filter_map(|organism| Some((organism.get_aggression()?, organism.get_hunger()?)))
+            .collect();
+        let mean = |total: f32| if brains.is_empty() { 0.0 } else { total / brains.len() as f32 };
+        let walls = sim.blocks.iter().filter(|block| block.block_type == BlockType::Wall).count();
+        Stats {
+            tick: sim.tick,
+            population: sim.organisms.len(),
+            food: sim.blocks.len() - walls,
+            walls,
+            cells,
+            mean_aggression: mean(brains.iter().map(|brain| brain.0).sum()),
+            mean_hunger: mean(brains.iter().map(|brain| brain.1).sum()),
+        }
+    }
+}
+
+pub struct Rate { // how fast a counter goes up, like frames or ticks per second
+    sample_start: Instant,
+    sample_count: Option<u64>,
+    per_second: f32,
+}
+
+impl Default for Rate {
+    fn default() -> Rate {
+        Rate::new()
+    }
+}
+
+impl Rate {
+    pub fn new() -> Rate {
+        Rate { sample_start: Instant::now(), sample_count: None, per_second: 0.0 }
+    }
+    pub fn update(&mut self, count: u64) -> f32 { // call often with the current value of the counter
+        let start_count = *self.sample_count.get_or_insert(count);
+        let elapsed = self.sample_start.elapsed().as_secs_f32();
+        if elapsed >= RATE_SAMPLE_SECONDS {
+            self.per_second = count.saturating_sub(start_count) as f32 / elapsed;
+            self.sample_start = Instant::now();
+            self.sample_count = Some(count);
+        }
+        self.per_second
+    }
+}
+
+pub struct History { // the most recent stats, one per tick
+    samples: VecDeque<Stats>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History { samples: VecDeque::with_capacity(capacity), capacity }
+    }
+    pub fn record(&mut self, stats: &Stats) { // ignores repeats of the last tick
+        if self.samples.back().is_some_and(|last| last.tick == stats.tick) {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(stats.clone());
+    }
+    pub fn samples(&self) -> &VecDeque<Stats> {
+        &self.samples
+    }
+}
@@ -0,0 +1,201 @@
+// procedural terrain made of wall blocks
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::block::{Block, BlockType};
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum TerrainKind {
+    Empty,
+    Hills,           // a noise heightmap with a solid floor
+    Caves,           // a solid cube with noise tunnels carved out of it
+    FloatingIslands, // noise blobs around the middle height, open above and below
+    Maze,            // full height corridors on a grid
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct TerrainParams {
+    pub kind: TerrainKind,
+    pub seed: u64,
+    pub size: u8,       // the terrain fills a cube this many voxels wide, centered on the origin
+    pub scale: f32,     // size of noise features, in voxels
+    pub density: f32,   // 0 to 1, how much of the volume is solid
+    pub corridor: u8,   // maze corridor width
+}
+
+impl TerrainParams {
+    pub fn new(kind: TerrainKind, seed: u64) -> TerrainParams {
+        TerrainParams {
+            kind,
+            seed,
+            size: 48,
+            scale: 12.0,
+            density: 0.5,
+            corridor: 4,
+        }
+    }
+}
+
+static SPAWN_CLEARANCE: i16 = 3; // keep the origin open for the first organism
+
+pub fn generate(params: &TerrainParams) -> Vec<Block> {
+    let half = params.size as i16 / 2;
+    let mut walls = Vec::new();
+    let mut add_wall = |x: i16, y: i16, z: i16| {
+        if x.abs() <= SPAWN_CLEARANCE && y.abs() <= SPAWN_CLEARANCE && z.abs() <= SPAWN_CLEARANCE {
+            return;
+        }
+        walls.push(Block::new(BlockType::Wall, x as i8, y as i8, z as i8));
+    };
+    match params.kind {
+        TerrainKind::Empty => {}
+        TerrainKind::Hills => {
+            for x in -half..half {
+                for z in -half..half {
+                    let noise = fractal_noise(params.seed, x as f32 / params.scale, 0.0, z as f32 / params.scale);
+                    let height = -half + ((noise * params.density).min(1.0) * params.size as f32) as i16;
+                    for y in -half..=height.min(half - 1) {
+                        add_wall(x, y, z);
+                    }
+                }
+            }
+        }
+        TerrainKind::Caves => {
+            for x in -half..half {
+                for y in -half..half {
+                    for z in -half..half {
+                        let noise = fractal_noise(params.seed, x as f32 / params.scale, y as f32 / params.scale, z as f32 / params.scale);
+                        if noise < params.density {
+                            add_wall(x, y, z);
+                        }
+                    }
+                }
+            }
+        }
+        TerrainKind::FloatingIslands => {
+            for x in -half..half {
+                for y in -half..half {
+                    for z in -half..half {
+                        let noise = fractal_noise(params.seed, x as f32 / params.scale, y as f32 / params.scale, z as f32 / params.scale);
+                        let falloff = (y as f32 / half as f32).abs(); // thins out towards the top and bottom
+                        if noise - falloff > 1.0 - params.density {
+                            add_wall(x, y, z);
+                        }
+                    }
+                }
+            }
+        }
+        TerrainKind::Maze => {
+            let corridor = params.corridor.max(1) as i16;
+            let cells = (params.size as i16 / (corridor + 1)).max(1) as usize;
+            let open = carve_maze(params.seed, cells);
+            let origin = -(cells as i16 * (corridor + 1)) / 2;
+            for x in 0..(cells as i16 * (corridor + 1) + 1) {
+                for z in 0..(cells as i16 * (corridor + 1) + 1) {
+                    if !is_maze_wall(&open, cells, corridor, x, z) {
+                        continue;
+                    }
+                    for y in -half..half {
+                        add_wall(origin + x, y, origin + z);
+                    }
+                }
+            }
+        }
+    }
+    walls
+}
+
+// maze carved by a randomized depth first search over a cells x cells grid
+// open[cell] holds whether the passage to the +x and +z neighbour is open
+fn carve_maze(seed: u64, cells: usize) -> Vec<(bool, bool)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut open = vec![(false, false); cells * cells];
+    let mut visited = vec![false; cells * cells];
+    let mut stack = vec![(0usize, 0usize)];
+    visited[0] = true;
+    while let Some(&(cx, cz)) = stack.last() {
+        let mut neighbours = Vec::new();
+        if cx > 0 && !visited[(cx - 1) * cells + cz] { neighbours.push((cx - 1, cz)); }
+        if cx + 1 < cells && !visited[(cx + 1) * cells + cz] { neighbours.push((cx + 1, cz)); }
+        if cz > 0 && !visited[cx * cells + cz - 1] { neighbours.push((cx, cz - 1)); }
+        if cz + 1 < cells && !visited[cx * cells + cz + 1] { neighbours.push((cx, cz + 1)); }
+        match neighbours.choose(&mut rng) {
+            Some(&(nx, nz)) => {
+                // the passage is stored on whichever of the two cells is lower
+                let (lx, lz) = (cx.min(nx), cz.min(nz));
+                if nx != cx { open[lx * cells + lz].0 = true; } else { open[lx * cells + lz].1 = true; }
+                visited[nx * cells + nz] = true;
+                stack.push((nx, nz));
+            }
+            None => { stack.pop(); }
+        }
+    }
+    open
+}
+
+fn is_maze_wall(open: &[(bool, bool)], cells: usize, corridor: i16, x: i16, z: i16) -> bool {
+    let pitch = corridor + 1;
+    let on_x_line = x % pitch == 0;
+    let on_z_line = z % pitch == 0;
+    if on_x_line && on_z_line {
+        return true; // pillars between cells are always solid
+    }
+    if !on_x_line && !on_z_line {
+        return false; // inside a cell
+    }
+    // a wall segment between two cells, open if the maze passes through it
+    let (cx, cz) = ((x / pitch) as usize, (z / pitch) as usize);
+    if on_x_line {
+        cx == 0 || cx >= cells || !open[(cx - 1) * cells + cz.min(cells - 1)].0
+    } else {
+        cz == 0 || cz >= cells || !open[cx.min(cells - 1) * cells + cz - 1].1
+    }
+}
+
+// value noise: random values on an integer lattice, smoothly interpolated
+fn lattice_value(seed: u64, x: i32, y: i32, z: i32) -> f32 {
+    let mut hash = seed ^ 0x9E37_79B9_7F4A_7C15;
+    for coord in [x, y, z] {
+        hash ^= coord as u32 as u64;
+        hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash ^= hash >> 31;
+    }
+    (hash >> 40) as f32 / (1u64 << 24) as f32 // top 24 bits as a fraction
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+pub fn value_noise(seed: u64, x: f32, y: f32, z: f32) -> f32 { // 0 to 1
+    let (x0, y0, z0) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+    let (tx, ty, tz) = (smoothstep(x - x0 as f32), smoothstep(y - y0 as f32), smoothstep(z - z0 as f32));
+    let corner = |dx: i32, dy: i32, dz: i32| lattice_value(seed, x0 + dx, y0 + dy, z0 + dz);
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), tx);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), tx);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), tx);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), tx);
+    lerp(lerp(x00, x10, ty), lerp(x01, x11, ty), tz)
+}
+
+pub fn fractal_noise(seed: u64, x: f32, y: f32, z: f32) -> f32 { // three octaves of value noise, 0 to 1
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut max = 0.0;
+    for octave in 0..3 {
+        total += value_noise(seed.wrapping_add(octave), x * frequency, y * frequency, z * frequency) * amplitude;
+        max += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    total / max
+}
@@ -0,0 +1,165 @@
+// a terminal frontend: the world as text, with live numbers and the runner's controls
+
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use rand::Rng;
+use crate::cell::CellType;
+use crate::runner::Runner;
+use crate::simulation::Command;
+use crate::slice::{Axis, Content, Slice};
+use crate::stats::{Rate, Stats};
+
+static FRAME_INTERVAL: Duration = Duration::from_millis(100); // the screen is redrawn at least this often
+static HEADER_LINES: u16 = 4;
+static PAN_STEP: i32 = 8;
+static EMPTY_COLOR: Color = Color::DarkGrey;
+
+struct View {
+    axis: Axis,
+    slice: Option<i16>, // the plane shown, or None to show everything flattened along the axis
+    pan: (i32, i32),    // columns and rows the middle of the screen is moved from the middle of the world
+}
+
+struct RawTerminal; // raw mode and the alternate screen while it lives; dropping it puts the terminal back, on errors and panics too
+
+impl RawTerminal {
+    fn enter(stdout: &mut Stdout) -> io::Result<RawTerminal> {
+        terminal::enable_raw_mode()?;
+        let raw = RawTerminal; // from here on, an error still leaves through drop
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+        Ok(raw)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // nothing better to do with an error here than carry on putting back what can be
+        let _ = execute!(io::stdout(), ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+pub fn run(runner: &Runner) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let _raw = RawTerminal::enter(&mut stdout)?;
+    run_loop(runner, &mut stdout)
+}
+
+fn run_loop(runner: &Runner, stdout: &mut Stdout) -> io::Result<()> {
+    let mut view = View { axis: Axis::Y, slice: None, pan: (0, 0) };
+    let mut tick_rate = Rate::new();
+    loop {
+        draw(runner, &view, &mut tick_rate, stdout)?;
+        if !event::poll(FRAME_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let mut rng = rand::thread_rng();
+        let mut random_position = || (rng.gen_range(-32..32), rng.gen_range(-32..32), rng.gen_range(-32..32));
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char(' ') => runner.toggle_pause(),
+            KeyCode::Char('.') => runner.step(),
+            KeyCode::Char(',') => runner.rewind_by(-1),
+            KeyCode::Char('<') => runner.rewind_by(-10),
+            KeyCode::Char('>') => runner.rewind_by(10),
+            KeyCode::Char('b') => runner.fork(),
+            KeyCode::Char('+') | KeyCode::Char('=') => runner.faster(),
+            KeyCode::Char('-') => runner.slower(),
+            KeyCode::Char('x') => view.axis = Axis::X,
+            KeyCode::Char('y') => view.axis = Axis::Y,
+            KeyCode::Char('z') => view.axis = Axis::Z,
+            KeyCode::Char('s') => view.slice = if view.slice.is_some() { None } else { Some(0) },
+            KeyCode::Char(']') => view.slice = view.slice.map(|position| (position + 1).min(i8::MAX as i16)),
+            KeyCode::Char('[') => view.slice = view.slice.map(|position| (position - 1).max(i8::MIN as i16)),
+            KeyCode::Left => view.pan.0 -= PAN_STEP,
+            KeyCode::Right => view.pan.0 += PAN_STEP,
+            KeyCode::Up => view.pan.1 -= PAN_STEP,
+            KeyCode::Down => view.pan.1 += PAN_STEP,
+            KeyCode::Home => view.pan = (0, 0),
+            KeyCode::Char('n') => {
+                let (x, y, z) = random_position();
+                runner.apply(Command::Spawn { definition: None, x, y, z });
+            }
+            KeyCode::Char('m') => {
+                let (x, y, z) = random_position();
+                runner.apply(Command::Meteor { x, y, z, radius: 8 });
+            }
+            KeyCode::Char('c') => runner.apply(Command::Cull { fraction: 0.5 }),
+            _ => {}
+        }
+    }
+}
+
+fn draw(runner: &Runner, view: &View, tick_rate: &mut Rate, stdout: &mut Stdout) -> io::Result<()> {
+    let (stats, slice) = {
+        let sim = runner.snapshot();
+        let slice = match view.slice {
+            Some(position) => Slice::take(&sim, view.axis, position),
+            None => Slice::project(&sim, view.axis),
+        };
+        (Stats::collect(&sim), slice)
+    };
+    let (columns, rows) = terminal::size()?;
+
+    let cells: Vec<String> = CellType::all().iter().map(|cell_type| format!("{} {}", cell_type.name(), stats.cells[cell_type.index()])).collect();
+    let shown = match view.slice {
+        Some(position) => format!("slice {:?} = {}", view.axis, position).to_lowercase(),
+        None => format!("everything along {:?}", view.axis).to_lowercase(),
+    };
+    let header = [
+        format!("tick {} ({:.1}/s)  speed {}", stats.tick, tick_rate.update(stats.tick), runner.describe()),
+        format!("organisms {}  food {}  walls {}  {}", stats.population, stats.food, stats.walls, cells.join(" ")),
+        format!("showing {}", shown),
+        "q quit  space pause  . step  ,/< back  b fork  +/- speed  x/y/z axis  s slice  [/] move slice  arrows pan  n spawn  m meteor  c cull".to_string(),
+    ];
+    queue!(stdout, MoveTo(0, 0), ResetColor)?;
+    for line in &header {
+        let line: String = line.chars().take(columns as usize).collect();
+        queue!(stdout, Print(line), Clear(ClearType::UntilNewLine), Print("\r\n"))?;
+    }
+
+    // the middle of the screen shows the middle of the slice, moved by the pan
+    let (width, height) = (columns as i32, rows.saturating_sub(HEADER_LINES) as i32);
+    let left = slice.width as i32 / 2 + view.pan.0 - width / 2;
+    let top = slice.height as i32 / 2 + view.pan.1 - height / 2;
+    for row in top..top + height {
+        let mut color = None;
+        for column in left..left + width {
+            let inside = column >= 0 && row >= 0 && (column as usize) < slice.width && (row as usize) < slice.height;
+            let (symbol, cell_color) = if inside {
+                let content = slice.get(column as usize, row as usize);
+                (content.symbol(), terminal_color(content))
+            } else {
+                (' ', EMPTY_COLOR)
+            };
+            if color != Some(cell_color) {
+                queue!(stdout, SetForegroundColor(cell_color))?;
+                color = Some(cell_color);
+            }
+            queue!(stdout, Print(symbol))?;
+        }
+        if row + 1 < top + height {
+            queue!(stdout, Print("\r\n"))?;
+        }
+    }
+    queue!(stdout, ResetColor)?;
+    stdout.flush()
+}
+
+fn terminal_color(content: Content) -> Color {
+    match content.color() {
+        Some((r, g, b)) => Color::Rgb { r: (r * 255.0) as u8, g: (g * 255.0) as u8, b: (b * 255.0) as u8 },
+        None => EMPTY_COLOR,
+    }
+}
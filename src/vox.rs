@@ -0,0 +1,375 @@
+// reading and writing MagicaVoxel .vox files
+// MagicaVoxel's z axis points up, ours is y, so the two are swapped on the way in and out
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use crate::block::{Block, BlockType, FoodSource};
+use crate::cell::{Cell, CellType};
+use crate::organism::Organism;
+
+static FOOD_COLOR_TOLERANCE: f32 = 0.2; // how close a voxel's color has to be to a food color to become food
+
+pub struct VoxModel {
+    pub size: (u32, u32, u32),
+    pub voxels: Vec<[u8; 4]>, // x, y, z, color index
+}
+
+pub struct VoxFile {
+    pub models: Vec<VoxModel>,
+    pub palette: [[u8; 4]; 256], // rgba, indexed by color index; index 0 is empty space
+}
+
+// MagicaVoxel's palette for files without an RGBA chunk: a 6x6x6 color cube without black,
+// followed by ramps of red, green, blue and grey
+pub fn default_palette() -> [[u8; 4]; 256] {
+    let mut palette = [[0; 4]; 256];
+    let levels = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let mut i = 1;
+    for r in levels {
+        for g in levels {
+            for b in levels {
+                if r == 0 && g == 0 && b == 0 {
+                    continue;
+                }
+                palette[i] = [r, g, b, 0xff];
+                i += 1;
+            }
+        }
+    }
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    for channel in 0..4 {
+        for level in ramp {
+            palette[i] = match channel {
+                0 => [level, 0, 0, 0xff],
+                1 => [0, level, 0, 0xff],
+                2 => [0, 0, level, 0xff],
+                _ => [level, level, level, 0xff],
+            };
+            i += 1;
+        }
+    }
+    palette
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("unexpected end of .vox file"))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<VoxFile> {
+    if bytes.get(0..4) != Some(b"VOX ".as_slice()) {
+        return Err(invalid("not a .vox file"));
+    }
+    if bytes.get(8..12) != Some(b"MAIN".as_slice()) {
+        return Err(invalid("missing MAIN chunk"));
+    }
+    let main_content = read_u32(bytes, 12)? as usize;
+    let main_children = read_u32(bytes, 16)? as usize;
+    let end = (20 + main_content + main_children).min(bytes.len());
+
+    let mut file = VoxFile { models: Vec::new(), palette: default_palette() };
+    let mut size = None;
+    let mut offset = 20 + main_content;
+    while offset + 12 <= end {
+        let id = &bytes[offset..offset + 4];
+        let content_size = read_u32(bytes, offset + 4)? as usize;
+        let children_size = read_u32(bytes, offset + 8)? as usize;
+        let content = offset + 12;
+        match id {
+            b"SIZE" => {
+                size = Some((read_u32(bytes, content)?, read_u32(bytes, content + 4)?, read_u32(bytes, content + 8)?));
+            }
+            b"XYZI" => {
+                let count = read_u32(bytes, content)? as usize;
+                let data = bytes.get(content + 4..content + 4 + count * 4).ok_or_else(|| invalid("truncated XYZI chunk"))?;
+                let voxels = data.chunks_exact(4).map(|v| [v[0], v[1], v[2], v[3]]).collect();
+                let size = size.take().ok_or_else(|| invalid("XYZI chunk without a SIZE chunk"))?;
+                file.models.push(VoxModel { size, voxels });
+            }
+            b"RGBA" => {
+                let data = bytes.get(content..content + 256 * 4).ok_or_else(|| invalid("truncated RGBA chunk"))?;
+                // the chunk starts at color index 1
+                for (i, rgba) in data.chunks_exact(4).take(255).enumerate() {
+                    file.palette[i + 1] = [rgba[0], rgba[1], rgba[2], rgba[3]];
+                }
+            }
+            _ => {} // scene graph, materials and layers don't matter to us
+        }
+        offset = content + content_size + children_size;
+    }
+    if file.models.is_empty() {
+        return Err(invalid(".vox file has no models"));
+    }
+    Ok(file)
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(content);
+}
+
+pub fn serialize(file: &VoxFile) -> Vec<u8> {
+    let mut children = Vec::new();
+    for model in &file.models {
+        let mut size = Vec::new();
+        for dimension in [model.size.0, model.size.1, model.size.2] {
+            size.extend_from_slice(&dimension.to_le_bytes());
+        }
+        write_chunk(&mut children, b"SIZE", &size);
+        let mut xyzi = (model.voxels.len() as u32).to_le_bytes().to_vec();
+        for voxel in &model.voxels {
+            xyzi.extend_from_slice(voxel);
+        }
+        write_chunk(&mut children, b"XYZI", &xyzi);
+    }
+    let mut rgba = Vec::new();
+    for color in file.palette.iter().skip(1) {
+        rgba.extend_from_slice(color);
+    }
+    rgba.extend_from_slice(&[0; 4]); // the chunk always holds 256 entries
+    write_chunk(&mut children, b"RGBA", &rgba);
+
+    let mut out = b"VOX ".to_vec();
+    out.extend_from_slice(&150u32.to_le_bytes());
+    out.extend_from_slice(b"MAIN");
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    out.extend_from_slice(&children);
+    out
+}
+
+fn to_rgb(color: [u8; 4]) -> (f32, f32, f32) {
+    (color[0] as f32 / 255.0, color[1] as f32 / 255.0, color[2] as f32 / 255.0)
+}
+
+fn to_rgba(color: (f32, f32, f32)) -> [u8; 4] {
+    [(color.0 * 255.0) as u8, (color.1 * 255.0) as u8, (color.2 * 255.0) as u8, 0xff]
+}
+
+fn distance(a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+}
+
+// voxels close to a food color become food, everything else is wall
+// the model is centered on the origin horizontally and sits on y = 0
+pub fn load_terrain(path: &str) -> Result<Vec<Block>> {
+    let file = parse(&fs::read(path)?)?;
+    let food_types = [BlockType::Food(FoodSource::Producer), BlockType::Food(FoodSource::Carcass)];
+    let mut blocks = Vec::new();
+    for model in &file.models {
+        let (half_x, half_y) = (model.size.0 as i32 / 2, model.size.1 as i32 / 2);
+        for voxel in &model.voxels {
+            let color = to_rgb(file.palette[voxel[3] as usize]);
+            let block_type = food_types.iter()
+                .find(|food| distance(food.color(), color) < FOOD_COLOR_TOLERANCE)
+                .cloned()
+                .unwrap_or(BlockType::Wall);
+            let (x, y, z) = (voxel[0] as i32 - half_x, voxel[2] as i32, voxel[1] as i32 - half_y);
+            if x > i8::MAX as i32 || y > i8::MAX as i32 || z > i8::MAX as i32 {
+                continue; // doesn't fit in the world
+            }
+            blocks.push(Block::new(block_type, x as i8, y as i8, z as i8));
+        }
+    }
+    Ok(blocks)
+}
+
+// every voxel becomes the cell type with the nearest color; the model needs exactly one brain voxel,
+// which becomes the organism's origin
+pub fn load_organism(path: &str) -> Result<Organism> {
+    let file = parse(&fs::read(path)?)?;
+    let model = &file.models[0];
+    let cell_types = CellType::all();
+    let typed: Vec<([u8; 4], CellType)> = model.voxels.iter().map(|voxel| {
+        let color = to_rgb(file.palette[voxel[3] as usize]);
+        let nearest = cell_types.iter()
+            .min_by(|a, b| distance(a.color(), color).total_cmp(&distance(b.color(), color)))
+            .unwrap();
+        (*voxel, nearest.clone())
+    }).collect();
+
+    let brains: Vec<&[u8; 4]> = typed.iter().filter(|(_, t)| matches!(t, CellType::Brain(_))).map(|(v, _)| v).collect();
+    let brain = match brains.as_slice() {
+        [brain] => **brain,
+        [] => return Err(invalid("organism model has no brain voxel")),
+        _ => return Err(invalid("organism model has more than one brain voxel")),
+    };
+
+    let mut organism = Organism::new();
+    organism.cells.clear();
+    for (voxel, cell_type) in typed {
+        let local = (voxel[0] as i32 - brain[0] as i32, voxel[2] as i32 - brain[2] as i32, voxel[1] as i32 - brain[1] as i32);
+        let fits = |v: i32| v >= i8::MIN as i32 && v <= i8::MAX as i32;
+        if !(fits(local.0) && fits(local.1) && fits(local.2)) {
+            return Err(invalid("organism model is too large"));
+        }
+        let cell = Cell::new(cell_type, 0, local.0 as i8, local.1 as i8, local.2 as i8);
+        if matches!(cell.cell_type, CellType::Brain(_)) {
+            organism.cells.insert(0, cell); // the brain is always the first cell
+        } else {
+            organism.cells.push(cell);
+        }
+    }
+    Ok(organism)
+}
+
+// our colors take the first palette slots, the rest keeps MagicaVoxel's defaults
+fn export_palette() -> [[u8; 4]; 256] {
+    let mut palette = default_palette();
+    let colors = CellType::all().into_iter().map(|t| t.color())
+        .chain([BlockType::Food(FoodSource::Producer), BlockType::Food(FoodSource::Carcass), BlockType::Wall].into_iter().map(|t| t.color()));
+    for (i, color) in colors.enumerate() {
+        palette[i + 1] = to_rgba(color);
+    }
+    palette
+}
+
+fn cell_color_index(cell_type: &CellType) -> u8 {
+    CellType::all().iter().position(|t| std::mem::discriminant(t) == std::mem::discriminant(cell_type)).unwrap() as u8 + 1
+}
+
+fn block_color_index(block_type: &BlockType) -> u8 {
+    let offset = CellType::all().len() as u8 + 1;
+    match block_type {
+        BlockType::Food(FoodSource::Producer) => offset,
+        BlockType::Food(FoodSource::Carcass) => offset + 1,
+        BlockType::Wall => offset + 2,
+    }
+}
+
+fn build_model(points: Vec<(i8, i8, i8, u8)>) -> VoxModel {
+    if points.is_empty() {
+        return VoxModel { size: (1, 1, 1), voxels: Vec::new() };
+    }
+    let min = |axis: fn(&(i8, i8, i8, u8)) -> i8| points.iter().map(axis).min().unwrap() as i32;
+    let max = |axis: fn(&(i8, i8, i8, u8)) -> i8| points.iter().map(axis).max().unwrap() as i32;
+    let (min_x, min_y, min_z) = (min(|p| p.0), min(|p| p.1), min(|p| p.2));
+    let (max_x, max_y, max_z) = (max(|p| p.0), max(|p| p.1), max(|p| p.2));
+    let voxels = points.iter()
+        .map(|p| [(p.0 as i32 - min_x) as u8, (p.2 as i32 - min_z) as u8, (p.1 as i32 - min_y) as u8, p.3])
+        .collect();
+    VoxModel {
+        size: ((max_x - min_x + 1) as u32, (max_z - min_z + 1) as u32, (max_y - min_y + 1) as u32),
+        voxels,
+    }
+}
+
+fn organism_points(organism: &Organism) -> impl Iterator<Item = (i8, i8, i8, u8)> + '_ {
+    organism.cells.iter().map(move |cell| (
+        organism.x.wrapping_add(cell.local_x),
+        organism.y.wrapping_add(cell.local_y),
+        organism.z.wrapping_add(cell.local_z),
+        cell_color_index(&cell.cell_type),
+    ))
+}
+
+pub fn save_organism(path: &str, organism: &Organism) -> Result<()> {
+    let file = VoxFile { models: vec![build_model(organism_points(organism).collect())], palette: export_palette() };
+    fs::write(path, serialize(&file))
+}
+
+pub fn save_world(path: &str, organisms: &[Organism], blocks: &[Block]) -> Result<()> {
+    let mut points: Vec<(i8, i8, i8, u8)> = organisms.iter().flat_map(organism_points).collect();
+    points.extend(blocks.iter().map(|block| (block.x, block.y, block.z, block_color_index(&block.block_type))));
+    let file = VoxFile { models: vec![build_model(points)], palette: export_palette() };
+    fs::write(path, serialize(&file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("life_3d_{}_{}.vox", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    fn shape(cells: &[Cell]) -> Vec<(usize, i8, i8, i8)> { // what survives the trip: types and places, not rotations or brain settings
+        let mut shape: Vec<_> = cells.iter().map(|cell| (cell.cell_type.index(), cell.local_x, cell.local_y, cell.local_z)).collect();
+        shape.sort();
+        shape
+    }
+
+    #[test]
+    fn organisms_round_trip() {
+        let mut organism = Organism::new();
+        organism.cells.extend([
+            Cell::new(CellType::Eye(crate::cell::Eye {}), 0, 1, 0, 0),
+            Cell::new(CellType::Mover, 0, 0, -1, 0),
+            Cell::new(CellType::Killer, 0, 0, 0, 1),
+            Cell::new(CellType::Producer(crate::cell::Producer {}), 0, -1, 1, -1),
+        ]);
+        (organism.x, organism.y, organism.z) = (5, -3, 7); // where it is doesn't matter, only where its cells are around the brain
+        let path = temp_path("organism");
+        save_organism(&path, &organism).unwrap();
+        let loaded = load_organism(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert!(matches!(loaded.cells[0].cell_type, CellType::Brain(_)));
+        assert_eq!(shape(&loaded.cells), shape(&organism.cells));
+    }
+
+    #[test]
+    fn models_without_a_brain_are_rejected() {
+        let mut organism = Organism::new();
+        organism.cells = vec![Cell::new(CellType::Armor, 0, 0, 0, 0), Cell::new(CellType::Eater, 0, 1, 0, 0)];
+        let path = temp_path("brainless");
+        save_organism(&path, &organism).unwrap();
+        let loaded = load_organism(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn worlds_round_trip() {
+        let blocks = vec![
+            Block::new(BlockType::Wall, 0, 0, 0),
+            Block::new(BlockType::Wall, 2, 1, -1),
+            Block::new(BlockType::Food(FoodSource::Producer), -2, 3, 1),
+            Block::new(BlockType::Food(FoodSource::Carcass), 1, 2, 2),
+        ];
+        let path = temp_path("world");
+        save_world(&path, &[], &blocks).unwrap();
+        let loaded = load_terrain(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        // the terrain comes back moved to sit on y = 0, centered on the origin, so compare it relative to its corner
+        let normalized = |blocks: &[Block]| {
+            let low = |axis: fn(&Block) -> i8| blocks.iter().map(axis).min().unwrap();
+            let (x, y, z) = (low(|b| b.x), low(|b| b.y), low(|b| b.z));
+            let mut placed: Vec<_> = blocks.iter().map(|b| (b.x - x, b.y - y, b.z - z, type_name(&b.block_type))).collect();
+            placed.sort();
+            placed
+        };
+        assert_eq!(normalized(&loaded), normalized(&blocks));
+    }
+
+    fn type_name(block_type: &BlockType) -> &'static str {
+        match block_type {
+            BlockType::Food(FoodSource::Producer) => "producer food",
+            BlockType::Food(FoodSource::Carcass) => "carcass",
+            BlockType::Wall => "wall",
+        }
+    }
+
+    #[test]
+    fn food_colors_load_as_food() {
+        let mut palette = default_palette();
+        palette[1] = to_rgba(BlockType::Food(FoodSource::Producer).color());
+        palette[2] = to_rgba(BlockType::Food(FoodSource::Carcass).color());
+        palette[3] = to_rgba(BlockType::Wall.color());
+        let model = VoxModel { size: (3, 1, 1), voxels: vec![[0, 0, 0, 1], [1, 0, 0, 2], [2, 0, 0, 3]] };
+        let path = temp_path("food");
+        fs::write(&path, serialize(&VoxFile { models: vec![model], palette })).unwrap();
+        let loaded = load_terrain(&path);
+        fs::remove_file(&path).unwrap();
+        let types: Vec<&str> = loaded.unwrap().iter().map(|block| type_name(&block.block_type)).collect();
+        assert_eq!(types, ["producer food", "carcass", "wall"]);
+    }
+}
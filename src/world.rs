@@ -1,7 +1,11 @@
 // defines the world and its components
-use crate::organism::Organism;
-use crate::block::Block;
+use crate::block::{Block, BlockType};
 use crate::cell::Cell;
+use crate::field::{Field, FieldKind};
+use crate::spatial::FoodIndex;
+
+pub const CHUNK_SIZE: usize = 16; // voxels along each side of a chunk
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
 
 #[derive(Clone)]
 pub enum Entity {
@@ -9,38 +13,149 @@ pub enum Entity {
     Cell(Cell),
 }
 
+#[derive(Clone)]
+struct Chunk {
+    voxels: Vec<Option<Entity>>, // CHUNK_VOLUME of them, x-major like everything else
+    occupied: usize,             // the chunk is dropped when this gets back to 0
+}
+
+#[derive(Clone)]
 pub struct World {
-    pub grid: Vec<Vec<Vec<Option<Entity>>>>,
+    chunks: Vec<Option<Chunk>>, // flat, x-major; only chunks with something in them are allocated
+    chunks_across: (usize, usize, usize),
+    pub fields: Vec<Field>, // one per FieldKind, in FieldKind::ALL order
+    pub food: FoodIndex,    // food isn't in the grid, several blocks can share a voxel and they can be outside it
     pub width: usize,
     pub height: usize,
     pub depth: usize,
 }
 impl World {
     pub fn new(width: usize, height: usize, depth: usize) -> World {
-        let grid = vec![vec![vec![None; depth]; height]; width]; // declares a 3D grid of empty entities
+        let chunks_across = (width.div_ceil(CHUNK_SIZE), height.div_ceil(CHUNK_SIZE), depth.div_ceil(CHUNK_SIZE));
+        let fields = FieldKind::ALL.iter().map(|kind| {
+            let (diffusion, decay, baseline) = kind.default_params();
+            Field::new(width, height, depth, diffusion, decay, baseline)
+        }).collect();
         World {
-            grid,
+            chunks: vec![None; chunks_across.0 * chunks_across.1 * chunks_across.2],
+            chunks_across,
+            fields,
+            food: FoodIndex::default(),
             width,
             height,
             depth,
         }
     }
-    pub fn set_entity(&mut self, x: usize, y: usize, z: usize, entity: Option<Entity>) {
-        if x < self.width && y < self.height && z < self.depth {
-            self.grid[x][y][z] = entity;
+    pub fn to_grid(&self, x: i8, y: i8, z: i8) -> Option<(usize, usize, usize)> { // organism coordinates are centered on the middle of the grid
+        let gx = x as isize + (self.width / 2) as isize;
+        let gy = y as isize + (self.height / 2) as isize;
+        let gz = z as isize + (self.depth / 2) as isize;
+        if gx >= 0 && gy >= 0 && gz >= 0 && (gx as usize) < self.width && (gy as usize) < self.height && (gz as usize) < self.depth {
+            Some((gx as usize, gy as usize, gz as usize))
         } else {
+            None
+        }
+    }
+    pub fn place_block(&mut self, block: &Block) { // walls go in the grid, food in the food index
+        if block.block_type != BlockType::Wall {
+            self.food.add(block);
+        } else if let Some((gx, gy, gz)) = self.to_grid(block.x, block.y, block.z) {
+            self.set_entity(gx, gy, gz, Some(Entity::Block(block.clone())));
+        }
+    }
+    pub fn remove_block(&mut self, block: &Block) {
+        if block.block_type != BlockType::Wall {
+            self.food.remove(block);
+        } else if let Some((gx, gy, gz)) = self.to_grid(block.x, block.y, block.z) {
+            self.set_entity(gx, gy, gz, None);
+        }
+    }
+    pub fn is_wall(&self, x: i8, y: i8, z: i8) -> bool {
+        match self.to_grid(x, y, z) {
+            Some((gx, gy, gz)) => matches!(self.get_entity(gx, gy, gz), Some(Entity::Block(Block { block_type: BlockType::Wall, .. }))),
+            None => false,
+        }
+    }
+    pub fn field(&self, kind: FieldKind) -> &Field {
+        &self.fields[kind as usize]
+    }
+    pub fn field_mut(&mut self, kind: FieldKind) -> &mut Field {
+        &mut self.fields[kind as usize]
+    }
+    pub fn sample_field(&self, kind: FieldKind, x: i8, y: i8, z: i8) -> f32 { // outside the grid reads as the baseline
+        let field = self.field(kind);
+        match self.to_grid(x, y, z) {
+            Some((gx, gy, gz)) => field.get(gx, gy, gz),
+            None => field.baseline,
+        }
+    }
+    pub fn add_to_field(&mut self, kind: FieldKind, x: i8, y: i8, z: i8, amount: f32) {
+        if let Some((gx, gy, gz)) = self.to_grid(x, y, z) {
+            self.field_mut(kind).add(gx, gy, gz, amount);
+        }
+    }
+    pub fn take_from_field(&mut self, kind: FieldKind, x: i8, y: i8, z: i8, max_amount: f32) -> f32 {
+        match self.to_grid(x, y, z) {
+            Some((gx, gy, gz)) => self.field_mut(kind).take(gx, gy, gz, max_amount),
+            None => 0.0,
+        }
+    }
+    pub fn update_fields(&mut self) {
+        for field in &mut self.fields {
+            field.update();
+        }
+    }
+    fn locate(&self, x: usize, y: usize, z: usize) -> (usize, usize) { // which chunk, and where in it
+        let (_, chunks_y, chunks_z) = self.chunks_across;
+        let chunk = ((x / CHUNK_SIZE) * chunks_y + y / CHUNK_SIZE) * chunks_z + z / CHUNK_SIZE;
+        let voxel = ((x % CHUNK_SIZE) * CHUNK_SIZE + y % CHUNK_SIZE) * CHUNK_SIZE + z % CHUNK_SIZE;
+        (chunk, voxel)
+    }
+    pub fn set_entity(&mut self, x: usize, y: usize, z: usize, entity: Option<Entity>) {
+        if x >= self.width || y >= self.height || z >= self.depth {
             // println!("OOB in set_entity ");
+            return;
+        }
+        let (chunk, voxel) = self.locate(x, y, z);
+        let slot = &mut self.chunks[chunk];
+        if slot.is_none() && entity.is_none() {
+            return; // clearing a voxel in an empty chunk, nothing to do
+        }
+        let stored = slot.get_or_insert_with(|| Chunk { voxels: vec![None; CHUNK_VOLUME], occupied: 0 });
+        match (stored.voxels[voxel].is_some(), entity.is_some()) {
+            (false, true) => stored.occupied += 1,
+            (true, false) => stored.occupied -= 1,
+            _ => {}
+        }
+        stored.voxels[voxel] = entity;
+        if stored.occupied == 0 {
+            *slot = None;
         }
         // println!("Entity set at {}, {}, {}", x, y, z);
     }
 
     pub fn get_entity(&self, x: usize, y: usize, z: usize) -> Option<&Entity> {
-        if x < self.width && y < self.height && z < self.depth {
-            self.grid[x][y][z].as_ref()
-        } else {
+        if x >= self.width || y >= self.height || z >= self.depth {
             // print!("OOB in get_entity ");
-            None
+            return None;
         }
+        let (chunk, voxel) = self.locate(x, y, z);
+        self.chunks[chunk].as_ref()?.voxels[voxel].as_ref()
+    }
+    pub fn entities(&self) -> impl Iterator<Item = ((usize, usize, usize), &Entity)> { // every occupied voxel, skipping empty chunks
+        let (_, chunks_y, chunks_z) = self.chunks_across;
+        self.chunks.iter().enumerate()
+            .filter_map(|(i, chunk)| chunk.as_ref().map(|chunk| (i, chunk)))
+            .flat_map(move |(i, chunk)| {
+                let origin = (i / (chunks_y * chunks_z) * CHUNK_SIZE, i / chunks_z % chunks_y * CHUNK_SIZE, i % chunks_z * CHUNK_SIZE);
+                chunk.voxels.iter().enumerate().filter_map(move |(j, voxel)| {
+                    let position = (origin.0 + j / (CHUNK_SIZE * CHUNK_SIZE), origin.1 + j / CHUNK_SIZE % CHUNK_SIZE, origin.2 + j % CHUNK_SIZE);
+                    voxel.as_ref().map(|entity| (position, entity))
+                })
+            })
+    }
+    pub fn allocated_chunks(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.is_some()).count()
     }
     pub fn get_adjacent_entities(&self, x: usize, y: usize, z: usize) -> Vec<((usize, usize, usize), &Entity)> {
         let mut adjacent_entities = Vec::new();
@@ -69,7 +184,68 @@ impl World {
         adjacent_entities
     }
     pub fn clear(&mut self) {
-        self.grid = vec![vec![vec![None; self.depth]; self.height]; self.width];
+        for chunk in &mut self.chunks {
+            *chunk = None;
+        }
         // println!("World was cleared");
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_come_and_go_with_what_is_in_them() {
+        let mut world = World::new(40, 40, 40); // not a whole number of chunks across
+        assert_eq!(world.allocated_chunks(), 0);
+        let wall = || Some(Entity::Block(Block::new(BlockType::Wall, 0, 0, 0)));
+        world.set_entity(0, 0, 0, wall());
+        world.set_entity(15, 15, 15, wall()); // same chunk
+        assert_eq!(world.allocated_chunks(), 1);
+        world.set_entity(16, 0, 0, wall());
+        world.set_entity(39, 39, 39, wall()); // the partial chunk at the far corner
+        assert_eq!(world.allocated_chunks(), 3);
+        world.set_entity(40, 0, 0, wall()); // off the grid, nothing happens
+        world.set_entity(5, 5, 5, None);    // clearing what's empty doesn't allocate
+        assert_eq!(world.allocated_chunks(), 3);
+        world.set_entity(0, 0, 0, wall());  // replacing isn't counted twice
+        world.set_entity(0, 0, 0, None);
+        assert_eq!(world.allocated_chunks(), 3);
+        world.set_entity(15, 15, 15, None);
+        assert_eq!(world.allocated_chunks(), 2);
+        world.set_entity(16, 0, 0, None);
+        world.set_entity(39, 39, 39, None);
+        assert_eq!(world.allocated_chunks(), 0);
+        assert_eq!(world.entities().count(), 0);
+    }
+
+    #[test]
+    fn entities_are_exactly_the_occupied_voxels() {
+        let mut world = World::new(64, 64, 64); // -32 to 31, so -16, 0 and 16 start chunks
+        let mut walls: Vec<(i8, i8, i8)> = Vec::new();
+        for x in [-32, -17, -16, -1, 0, 15, 16, 31] {
+            for (y, z) in [(-1, 0), (0, -1), (-16, 15), (31, -32)] {
+                walls.push((x, y, z));
+            }
+        }
+        for &(x, y, z) in &walls {
+            world.place_block(&Block::new(BlockType::Wall, x, y, z));
+        }
+        world.place_block(&Block::new(BlockType::Wall, 32, 0, 0));   // off the grid
+        world.place_block(&Block::new(BlockType::Wall, -33, 0, 0));
+        world.place_block(&Block::new(BlockType::Food(crate::block::FoodSource::Producer), 1, 1, 1)); // food isn't in the grid
+        let removed = walls.remove(3);
+        world.remove_block(&Block::new(BlockType::Wall, removed.0, removed.1, removed.2));
+
+        let mut found: Vec<(usize, usize, usize)> = world.entities().map(|(position, _)| position).collect();
+        found.sort_unstable();
+        let mut expected: Vec<(usize, usize, usize)> = walls.iter().map(|&(x, y, z)| world.to_grid(x, y, z).unwrap()).collect();
+        expected.sort_unstable();
+        assert_eq!(found, expected);
+        assert!(world.entities().all(|((x, y, z), entity)| matches!(entity, Entity::Block(block) if world.to_grid(block.x, block.y, block.z) == Some((x, y, z)))));
+        for &(x, y, z) in &walls {
+            assert!(world.is_wall(x, y, z));
+        }
+        assert!(!world.is_wall(removed.0, removed.1, removed.2));
+    }
+}